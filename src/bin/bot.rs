@@ -0,0 +1,45 @@
+use std::thread;
+
+use snake::new::bot;
+use snake::old::client::Client;
+
+const USAGE: &str = "usage: bot <server address> <game name> [count] [strategy]";
+
+/// Headless robot players for load testing: every bot is a separate UDP client on its own thread.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (Some(addr), Some(game_name)) = (args.first(), args.get(1)) else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let count = match args.get(2).map(|count| count.parse::<usize>()) {
+        None => 1,
+        Some(Ok(count)) => count,
+        Some(Err(_)) => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let strategy = args.get(3).map_or(bot::DEFAULT_STRATEGY, |strategy| strategy.as_str());
+    if !bot::STRATEGIES.contains(&strategy) {
+        eprintln!("unknown strategy {}, expected one of {}", strategy, bot::STRATEGIES.join(", "));
+        std::process::exit(2);
+    }
+
+    let handles: Vec<_> = (1..=count).map(|i| {
+        let addr = addr.clone();
+        let game_name = game_name.clone();
+        let strategy = strategy.to_owned();
+        thread::spawn(move || {
+            let player_name = format!("{} {}", strategy, i);
+            let strategy = bot::by_name(&strategy, rand::random()).expect("known strategy");
+            match Client::join_bot(addr.as_str(), &game_name, &player_name, strategy) {
+                Ok(client) => client.play(),
+                Err(e) => eprintln!("{}: failed to join: {}", player_name, e),
+            }
+        })
+    }).collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+}
@@ -0,0 +1,88 @@
+use std::process::exit;
+
+use anyhow::{bail, Result};
+use snake::new::bot;
+use snake::snakes::{ext, snakes::GameConfig};
+use snake::tournament::{self, Settings};
+
+const USAGE: &str = "usage: tournament [--matches N] [--seed N] [--ticks N] [--width N] [--height N] [--food N] \
+[--food-per-player F] [--walled] [--json PATH] [--csv PATH] [strategy...]";
+
+fn value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T> {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => Ok(value),
+        _ => bail!("{} needs a numeric value", flag),
+    }
+}
+
+fn parse() -> Result<(Settings, Option<String>, Option<String>)> {
+    let mut config = GameConfig::new();
+    config.set_width(40);
+    config.set_height(30);
+    config.set_food_static(10);
+    config.set_state_delay_ms(100);
+    let mut settings = Settings {
+        config,
+        strategies: Vec::new(),
+        matches: 100,
+        seed: 0,
+        max_ticks: 2000,
+        food_per_player: 0.0,
+    };
+    let mut json = None;
+    let mut csv = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--matches" => settings.matches = value(&arg, args.next())?,
+            "--seed" => settings.seed = value(&arg, args.next())?,
+            "--ticks" => settings.max_ticks = value(&arg, args.next())?,
+            "--width" => settings.config.set_width(value(&arg, args.next())?),
+            "--height" => settings.config.set_height(value(&arg, args.next())?),
+            "--food" => settings.config.set_food_static(value(&arg, args.next())?),
+            "--food-per-player" => settings.food_per_player = value(&arg, args.next())?,
+            "--walled" => ext::set_walled(&mut settings.config, true),
+            "--json" => json = Some(args.next().ok_or_else(|| anyhow::anyhow!("--json needs a path"))?),
+            "--csv" => csv = Some(args.next().ok_or_else(|| anyhow::anyhow!("--csv needs a path"))?),
+            flag if flag.starts_with("--") => bail!("unknown option {}", flag),
+            strategy => settings.strategies.push(strategy.to_owned()),
+        }
+    }
+    if settings.strategies.is_empty() {
+        settings.strategies = bot::STRATEGIES.iter().map(|strategy| strategy.to_string()).collect();
+    }
+    Ok((settings, json, csv))
+}
+
+/// Seeded matches between bot strategies on the engine alone, for comparing bots and tuning the config.
+fn main() {
+    let (settings, json, csv) = match parse() {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+    let stats = match tournament::run(&settings) {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    let table = tournament::to_csv(&stats);
+    print!("{}", table);
+    if let Some(path) = csv {
+        if let Err(e) = std::fs::write(&path, &table) {
+            eprintln!("failed to write {}: {}", path, e);
+            exit(1);
+        }
+    }
+    if let Some(path) = json {
+        let json = serde_json::to_string_pretty(&tournament::to_json(&settings, &stats)).expect("json from values");
+        if let Err(e) = std::fs::write(&path, json) {
+            eprintln!("failed to write {}: {}", path, e);
+            exit(1);
+        }
+    }
+}
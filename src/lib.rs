@@ -0,0 +1,6 @@
+pub mod tui;
+pub mod old;
+pub mod new;
+pub mod snakes;
+pub mod simulation;
+pub mod tournament;
@@ -1,13 +1,137 @@
 use ncurses::*;
-mod tui;
-mod old;
-mod snakes;
-use snakes::snakes::GameConfig;
-use tui::{menu, config};
+use snake::snakes::snakes::GameConfig;
+use snake::snakes::validate::{self, ConfigField};
+use snake::snakes::ext;
+use snake::new::base::game::MAX_TEAMS;
+use snake::new::bot;
+use snake::new::map::Map;
+use snake::tui::{menu, config};
 use config::NumInput;
-use old::server::*;
-use tui::browse::browse;
-use tui::dirrect::show_connect_dialog;
+use snake::old::server::*;
+use snake::tui::browse::browse;
+use snake::tui::dirrect::show_connect_dialog;
+use snake::tui::err::print_error;
+use snake::tui::modal::show_modal;
+
+const MAX_ROUND_SECONDS: i32 = 24 * 60 * 60;
+
+struct RoomSettings {
+    name: String,
+    config: GameConfig,
+    bots: usize,
+    strategy: String,
+}
+
+fn room_options() -> Vec<NumInput<'static>> {
+    vec![
+        NumInput::str_default("Server Name", "Snake game"),
+        NumInput::default("width", 10),
+        NumInput::default("height", 10),
+        NumInput::default("max food", 10),
+        NumInput::default("state delay ms", 500),
+        NumInput::default("max players (0 - no limit)", 0),
+        NumInput::default("food per player, %", 0),
+        NumInput::default("walled (0 - wrap around)", 0),
+        NumInput::str("map file (empty - no map)"),
+        NumInput::default("mode (0 endless, 1 timed, 2 first to N, 3 last standing)", 0),
+        NumInput::default("round seconds / score to win", 120),
+        NumInput::default("teams (0 - no teams)", 0),
+        NumInput::default("friendly fire (0 - teammates pass through)", 1),
+        NumInput::default("bots", 0),
+        NumInput::str_default("bot strategy (random, greedy, bfs, flood)", bot::DEFAULT_STRATEGY),
+    ]
+}
+
+/// Shows the form until it holds a valid room, None when it is cancelled.
+fn room_form(options: &mut Vec<NumInput>, rooms: &[RoomSettings]) -> Option<RoomSettings> {
+    while config::show_menu_config(options).is_ok() {
+        if rooms.iter().any(|room| room.name == options[0].raw) {
+            options[0].error = Some("another room has this name".to_owned());
+            continue;
+        }
+        let map = if options[8].raw.is_empty() {
+            None
+        } else {
+            match Map::load(&options[8].raw) {
+                Ok(map) => {
+                    // the map decides the field size
+                    for (input, size) in [(1, map.width), (2, map.height)] {
+                        options[input].value = size as i32;
+                        options[input].raw = size.to_string();
+                    }
+                    Some(map)
+                }
+                Err(e) => {
+                    options[8].error = Some(e.to_string());
+                    continue;
+                }
+            }
+        };
+        let mut cfg = GameConfig::new();
+        cfg.set_width(options[1].value);
+        cfg.set_height(options[2].value);
+        cfg.set_food_static(options[3].value);
+        cfg.set_state_delay_ms(options[4].value);
+        ext::set_walled(&mut cfg, options[7].value != 0);
+        ext::set_map(&mut cfg, map.map(|map| map.to_bytes()));
+        if !(0..=3).contains(&options[9].value) {
+            options[9].error = Some("must be in 0..=3".to_owned());
+            continue;
+        }
+        if matches!(options[9].value, 1 | 2) && options[10].value <= 0 {
+            options[10].error = Some("must be positive".to_owned());
+            continue;
+        }
+        if options[9].value == 1 && options[10].value > MAX_ROUND_SECONDS {
+            options[10].error = Some(format!("must be at most {} seconds", MAX_ROUND_SECONDS));
+            continue;
+        }
+        ext::set_mode(&mut cfg, options[9].value as u64, options[10].value.max(0) as u64);
+        if !(0..=MAX_TEAMS as i32).contains(&options[11].value) {
+            options[11].error = Some(format!("must be in 0..={}", MAX_TEAMS));
+            continue;
+        }
+        ext::set_teams(&mut cfg, options[11].value as u32, options[12].value != 0);
+        let errors = validate::validate(&cfg);
+        if !errors.is_empty() {
+            for error in errors {
+                let input = match error.field {
+                    ConfigField::Width => 1,
+                    ConfigField::Height => 2,
+                    ConfigField::FoodStatic => 3,
+                    ConfigField::StateDelayMs => 4,
+                };
+                options[input].error = Some(format!("must be in {}..={}", error.range.start(), error.range.end()));
+            }
+            continue;
+        }
+        if options[5].value < 0 {
+            options[5].error = Some("must not be negative".to_owned());
+            continue;
+        }
+        ext::set_max_players(&mut cfg, (options[5].value > 0).then_some(options[5].value as usize));
+        if options[6].value < 0 {
+            options[6].error = Some("must not be negative".to_owned());
+            continue;
+        }
+        ext::set_food_per_player(&mut cfg, options[6].value as f32 / 100f32);
+        if options[13].value < 0 {
+            options[13].error = Some("must not be negative".to_owned());
+            continue;
+        }
+        if !bot::STRATEGIES.contains(&options[14].raw.as_str()) {
+            options[14].error = Some(format!("must be one of {}", bot::STRATEGIES.join(", ")));
+            continue;
+        }
+        return Some(RoomSettings {
+            name: options[0].raw.clone(),
+            config: cfg,
+            bots: options[13].value as usize,
+            strategy: options[14].raw.clone(),
+        });
+    }
+    None
+}
 
 fn main() {
 
@@ -20,30 +144,40 @@ fn main() {
         if let Ok(option) = menu::show_menu(vec!["Start", "Server list", "Dirrect connect", "Exit"], &mut player_name) {
             match option {
                 "Start" => {
-                    let mut options = vec![
-                        NumInput::str_default("Server Name", "Snake game"),
-                        NumInput::default("width", 10),
-                        NumInput::default("height", 10),
-                        NumInput::default("max food", 10),
-                        NumInput::default("state delay ms", 500),
-                        ];
-                    if let Ok(_) = config::show_menu_config(&mut options) {
-                        let mut cfg = GameConfig::new();
-                        cfg.set_width(options[1].value);
-                        cfg.set_height(options[2].value);
-                        cfg.set_food_static(options[3].value);
-                        cfg.set_state_delay_ms(options[4].value);
-                        let mut srv = Server::new(cfg, options[0].raw.clone());
-                        srv.run(&player_name);
+                    let mut options = room_options();
+                    let mut rooms: Vec<RoomSettings> = Vec::new();
+                    while let Some(room) = room_form(&mut options, &rooms) {
+                        rooms.push(room);
+                        if show_modal("Add another room?", vec!["No", "Yes"]) != "Yes" {
+                            break;
+                        }
+                        // the next room starts from this one's settings
+                        options[0].raw = format!("{} {}", rooms[0].name, rooms.len() + 1);
+                    }
+                    let mut rooms = rooms.into_iter();
+                    let Some(first) = rooms.next() else {
+                        continue;
+                    };
+                    let mut srv = Server::new(first.config, first.name.clone());
+                    let added = srv.add_bots(&first.name, first.bots, &first.strategy).and_then(|_| {
+                        for room in rooms {
+                            srv.add_game(room.config, room.name.clone())?;
+                            srv.add_bots(&room.name, room.bots, &room.strategy)?;
+                        }
+                        Ok(())
+                    });
+                    match added {
+                        Ok(()) => srv.run(&player_name),
+                        Err(e) => print_error(e),
                     }
                 }
                 "Server list" => {
-                    if let Some(mut client) = browse(&player_name) {
+                    if let Some(client) = browse(&player_name) {
                         client.play();
                     }
                 },
                 "Dirrect connect" => {
-                    if let Some(mut client) = show_connect_dialog(&player_name) {
+                    if let Some(client) = show_connect_dialog(&player_name) {
                         client.play();
                     }
                 }
@@ -1,19 +1,98 @@
 
 pub mod game {
 
-use std::{collections::HashMap, time::Duration, cell::Cell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::time::Duration;
 
-use rand::rngs::ThreadRng;
-use rand::Rng;
+use rand::prelude::SliceRandom;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
+use crate::new::map::Map;
+use crate::new::spawn::{self, NoSpace, Spawn};
+
+const FOOD_SCORE: i32 = 10;
+const KILL_SCORE: i32 = 10;
+pub const MAX_TEAMS: u32 = 4;
+
+#[derive(Clone, Debug)]
 pub struct Config {
     pub width: usize,
     pub height: usize,
     pub food_count: usize,
-    pub state_delay: Duration
+    /// Extra food per alive snake on top of `food_count`.
+    pub food_per_player: f32,
+    pub state_delay: Duration,
+    /// Leaving the field kills the snake instead of wrapping to the other side.
+    pub walled: bool,
+    pub map: Option<Map>,
+    pub mode: Mode,
+    /// Number of teams, players are split among them on join; 0 plays every snake for itself.
+    pub teams: u32,
+    /// Teammates' bodies are lethal, otherwise snakes of one team pass through each other.
+    pub friendly_fire: bool,
+    /// Joins are refused once this many snakes are alive, viewers are always let in.
+    pub max_players: Option<usize>,
+}
+
+impl Config {
+    pub fn spawn_zone(&self) -> &[Coord] {
+        self.map.as_ref().map_or(&[], |map| &map.spawns)
+    }
+
+    /// The neighbouring cell, wrapped around the edges; None when it would leave a walled field.
+    pub fn step(&self, coord: Coord, direction: Direction) -> Option<Coord> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let (x, y) = match direction {
+            Direction::UP => (coord.x, coord.y - 1),
+            Direction::DOWN => (coord.x, coord.y + 1),
+            Direction::LEFT => (coord.x - 1, coord.y),
+            Direction::RIGHT => (coord.x + 1, coord.y),
+        };
+        if self.walled && !((0..width).contains(&x) && (0..height).contains(&y)) {
+            return None;
+        }
+        Some(Coord::new(x.rem_euclid(width), y.rem_euclid(height)))
+    }
+
+    /// Length of a timed round in ticks, so every node counts it the same way.
+    pub fn round_ticks(&self) -> Option<i32> {
+        match self.mode {
+            Mode::Timed(seconds) => {
+                let delay = self.state_delay.as_millis().max(1) as u64;
+                Some((seconds as u64 * 1000 / delay).clamp(1, i32::MAX as u64) as i32)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How a round ends; once it does the game restarts with fresh spawns and zero scores.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Mode {
+    #[default]
+    Endless,
+    /// Round length in seconds.
+    Timed(u32),
+    /// Score that wins the round.
+    FirstTo(i32),
+    /// Ends when at most one snake is left out of several.
+    LastStanding,
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Endless => write!(f, "endless"),
+            Mode::Timed(seconds) => write!(f, "timed {}s", seconds),
+            Mode::FirstTo(score) => write!(f, "first to {}", score),
+            Mode::LastStanding => write!(f, "last standing"),
+        }
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Coord {
     pub x: i32,
     pub y: i32,
@@ -25,14 +104,15 @@ impl Coord {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum WorldCell {
     Snake,
     Empty,
     Food,
+    Obstacle,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Direction {
     UP,
     DOWN,
@@ -40,161 +120,707 @@ pub enum Direction {
     RIGHT,
 }
 
+impl Direction {
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::UP => Direction::DOWN,
+            Direction::DOWN => Direction::UP,
+            Direction::LEFT => Direction::RIGHT,
+            Direction::RIGHT => Direction::LEFT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SnakeState {
+    Alive,
+    Zombie,
+}
+
+/// Body is stored from tail to head, so the head is the back of the deque.
+#[derive(Clone)]
 pub struct Snake {
     pub head_direction: Direction,
-    pub body: Vec<Coord>,
+    pub body: VecDeque<Coord>,
     pub id: i32,
+    pub state: SnakeState,
 }
 
+impl Snake {
+    pub fn head(&self) -> Option<Coord> {
+        self.body.back().copied()
+    }
+}
 
-pub trait World {
-    fn copy(&mut self, world: &Self);
-    fn get_free_random_coord(&self, rng: &mut ThreadRng) -> Coord;
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    Normal,
+    Master,
+    Deputy,
+    Viewer,
 }
 
-impl World for Vec<Vec<WorldCell>> {
-    fn copy(&mut self, world: &Self) {
-        for (i, row) in world.iter().enumerate() {
-            for (j, cell) in row.iter().enumerate() {
-                self[i][j] = *cell;
-            }
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlayerKind {
+    Human,
+    Robot,
+}
+
+#[derive(Clone, Debug)]
+pub struct Player {
+    pub id: i32,
+    pub name: String,
+    pub score: i32,
+    pub role: Role,
+    pub kind: PlayerKind,
+    /// Teams are numbered from 1.
+    pub team: Option<u32>,
+}
+
+impl Player {
+    pub fn new(id: i32, name: String, role: Role, kind: PlayerKind) -> Self {
+        Player { id, name, score: 0, role, kind, team: None }
+    }
+}
+
+#[derive(Default)]
+pub struct Tick {
+    pub dead: Vec<i32>,
+    pub eaten: Vec<(i32, Coord)>,
+}
+
+/// Final standings of a round, best score first.
+#[derive(Clone, Debug)]
+pub struct RoundSummary {
+    pub round: u32,
+    pub winner: Option<i32>,
+    pub standings: Vec<Player>,
+    pub winning_team: Option<u32>,
+    /// Team scores, best first; empty without teams.
+    pub teams: Vec<(u32, i32)>,
+}
+
+impl Display for RoundSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let winner = self.standings.iter().find(|player| Some(player.id) == self.winner);
+        match (self.winning_team, winner) {
+            (Some(team), _) => writeln!(f, "round {} won by team {}", self.round, team)?,
+            (None, Some(winner)) => writeln!(f, "round {} won by {}", self.round, winner.name)?,
+            (None, None) => writeln!(f, "round {} is a draw", self.round)?,
         }
+        for (team, score) in self.teams.iter() {
+            writeln!(f, "{}\tteam {}", score, team)?;
+        }
+        for player in self.standings.iter() {
+            writeln!(f, "{}\t{}", player.score, player.name)?;
+        }
+        Ok(())
     }
-    
-    // fn get_free_coords_shuffled(&self) -> Vec<(usize, usize)> {
-    //     let mut free_coords = Vec::<(usize, usize)>::new();
+}
 
-    //     for (i, row) in self.world.iter().enumerate() {
-    //         for (j, cell) in row.iter().enumerate() {
-    //             if *cell == Cell::None {
-    //                 free_coords.push((j, i));
-    //             }
-    //         }
-    //     }
-    //     let mut rng = self.rng.take();
-    //     free_coords.shuffle(&mut rng);
-    //     self.rng.set(rng);
-    //     free_coords
-    // }
+const ABSENT: usize = usize::MAX;
 
-    fn get_free_random_coord(&self, rng: &mut ThreadRng) -> Coord {
-        let coord = loop {
-            let row = rng.gen_range(0..self.len());
-            let col = rng.gen_range(0..self[0].len());
+/// Set of field cells with O(1) insert, remove and uniform random choice.
+#[derive(Clone)]
+pub struct CellSet {
+    cells: Vec<Coord>,
+    index: Vec<usize>,
+    width: usize,
+}
 
-            if self[row][col] == WorldCell::Empty {
-                let coord = Coord::new(col as i32, row as i32);
-                break coord;
-            }
-        };
-        coord
+impl CellSet {
+    pub fn new(width: usize, height: usize) -> Self {
+        CellSet {
+            cells: Vec::new(),
+            index: vec![ABSENT; width * height],
+            width,
+        }
+    }
+
+    fn slot(&self, coord: Coord) -> usize {
+        coord.y as usize * self.width + coord.x as usize
+    }
+
+    pub fn insert(&mut self, coord: Coord) -> bool {
+        let slot = self.slot(coord);
+        if self.index[slot] != ABSENT {
+            return false;
+        }
+        self.index[slot] = self.cells.len();
+        self.cells.push(coord);
+        true
+    }
+
+    pub fn remove(&mut self, coord: Coord) -> bool {
+        let slot = self.slot(coord);
+        let pos = self.index[slot];
+        if pos == ABSENT {
+            return false;
+        }
+        self.index[slot] = ABSENT;
+        self.cells.swap_remove(pos);
+        if let Some(moved) = self.cells.get(pos).copied() {
+            let moved_slot = self.slot(moved);
+            self.index[moved_slot] = pos;
+        }
+        true
+    }
+
+    pub fn contains(&self, coord: Coord) -> bool {
+        self.index[self.slot(coord)] != ABSENT
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Coord> {
+        self.cells.iter()
+    }
+
+    pub fn choose<R: Rng>(&self, rng: &mut R) -> Option<Coord> {
+        self.cells.choose(rng).copied()
+    }
+
+    pub fn clear(&mut self) {
+        for coord in std::mem::take(&mut self.cells) {
+            let slot = self.slot(coord);
+            self.index[slot] = ABSENT;
+        }
+    }
+}
+
+/// Ids of the snakes with a segment in a cell, once per segment.
+#[derive(Clone, Default)]
+struct Occupancy {
+    owners: Vec<i32>,
+}
+
+impl Occupancy {
+    fn count(&self) -> usize {
+        self.owners.len()
     }
 }
 
+/// `world` is the public view of the field, `occupancy` lists snake segments per cell
+/// (more than one only while a collision is being resolved) and `free` indexes empty cells.
+/// All three are updated in place, a tick touches only heads, tails and dead snakes.
 pub struct Game {
     pub world: Vec<Vec<WorldCell>>,
-    tmp_world: Vec<Vec<WorldCell>>,
-    pub snakes: HashMap<i32, Snake>,
-    pub food: Vec<Coord>,
+    pub snakes: BTreeMap<i32, Snake>,
+    pub food: CellSet,
+    pub players: BTreeMap<i32, Player>,
     pub config: Config,
-    rng: Cell<ThreadRng>,
+    pub order: i32,
+    pub round: u32,
+    /// Order of the first tick of the current round.
+    pub round_start: i32,
+    occupancy: Vec<Vec<Occupancy>>,
+    free: CellSet,
+    /// Players that had a snake this round, they get a new one on restart.
+    entrants: BTreeSet<i32>,
+    /// Last standing only ends a round that had more than one snake at some point.
+    contested: bool,
+    seed: u64,
+    rng: StdRng,
 }
 
 impl Game {
-    pub fn new(config: Config) -> Self {
+    pub fn new(config: Config, seed: u64) -> Self {
+        let mut world = vec![vec![WorldCell::Empty; config.width]; config.height];
+        for wall in config.map.iter().flat_map(|map| map.walls.iter()) {
+            world[wall.y as usize][wall.x as usize] = WorldCell::Obstacle;
+        }
+        let mut free = CellSet::new(config.width, config.height);
+        for (y, row) in world.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if *cell == WorldCell::Empty {
+                    free.insert(Coord::new(x as i32, y as i32));
+                }
+            }
+        }
         Game {
-            world: vec![vec![WorldCell::Empty; config.width]; config.height],
-            tmp_world: vec![vec![WorldCell::Empty; config.width]; config.height],
-            snakes: HashMap::new(),
-            food: Vec::with_capacity(config.food_count),
+            world,
+            snakes: BTreeMap::new(),
+            food: CellSet::new(config.width, config.height),
+            players: BTreeMap::new(),
+            occupancy: vec![vec![Occupancy::default(); config.width]; config.height],
+            free,
             config,
-            rng: Cell::new(rand::thread_rng()),
+            order: 0,
+            round: 1,
+            round_start: 0,
+            entrants: BTreeSet::new(),
+            contested: false,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts the random sequence over from `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn occupancy(&mut self, coord: Coord) -> &mut Occupancy {
+        &mut self.occupancy[coord.y as usize][coord.x as usize]
+    }
+
+    fn refresh(&mut self, coord: Coord) {
+        if self.world[coord.y as usize][coord.x as usize] == WorldCell::Obstacle {
+            return;
+        }
+        let cell = if self.occupancy(coord).count() > 0 {
+            WorldCell::Snake
+        } else if self.food.contains(coord) {
+            WorldCell::Food
+        } else {
+            WorldCell::Empty
+        };
+        self.world[coord.y as usize][coord.x as usize] = cell;
+        if cell == WorldCell::Empty {
+            self.free.insert(coord);
+        } else {
+            self.free.remove(coord);
+        }
+    }
+
+    fn occupy(&mut self, coord: Coord, owner: i32) {
+        self.occupancy(coord).owners.push(owner);
+        self.refresh(coord);
+    }
+
+    fn vacate(&mut self, coord: Coord, owner: i32) {
+        let owners = &mut self.occupancy(coord).owners;
+        if let Some(index) = owners.iter().position(|id| *id == owner) {
+            owners.swap_remove(index);
         }
+        self.refresh(coord);
+    }
+
+    pub fn has_free_coord5x5(&self) -> bool {
+        spawn::has_space(&self.world, self.config.walled, self.config.spawn_zone())
+    }
+
+    pub fn get_free_random_coord(&mut self) -> Option<Coord> {
+        self.free.choose(&mut self.rng)
     }
 
     pub fn add_snake(&mut self, snake: Snake) {
         for e in snake.body.iter() {
-            self.world[e.y][e.x] = 
+            self.occupy(*e, snake.id);
         }
+        self.entrants.insert(snake.id);
         self.snakes.insert(snake.id, snake);
     }
 
-    pub fn tick(&mut self) {
-        let snakes = &mut self.snakes;
-        let world = &mut self.world;
-        let new_world = &mut self.tmp_world;
-        snakes.retain(|id, snake| {
-            if let Some(head) = snake.body.last() {
-                let mut new_x = head.x;
-                let mut new_y = head.y;
-                match snake.head_direction {
-                    Direction::UP => new_y -= 1,
-                    Direction::DOWN => new_y += 1,
-                    Direction::LEFT => new_x -= 1,
-                    Direction::RIGHT => new_x += 1
+    pub fn spawn_snake(&mut self, id: i32) -> Result<(), NoSpace> {
+        let Spawn { head, tail, direction } = spawn::find(&self.world, self.config.walled, self.config.spawn_zone(), &mut self.rng)?;
+        self.add_snake(Snake {
+            head_direction: direction,
+            body: VecDeque::from([tail, head]),
+            id,
+            state: SnakeState::Alive,
+        });
+        Ok(())
+    }
+
+    pub fn add_food(&mut self, coord: Coord) {
+        self.food.insert(coord);
+        self.refresh(coord);
+    }
+
+    pub fn food_target(&self) -> usize {
+        self.config.food_count + (self.config.food_per_player * self.alive() as f32) as usize
+    }
+
+    pub fn can_steer(&self, id: i32, direction: Direction) -> bool {
+        self.snakes.get(&id).is_some_and(|snake| direction != snake.head_direction.opposite())
+    }
+
+    fn clear_field(&mut self) {
+        let snakes = std::mem::take(&mut self.snakes);
+        for snake in snakes.values() {
+            for coord in snake.body.iter() {
+                self.vacate(*coord, snake.id);
+            }
+        }
+        let food: Vec<Coord> = self.food.iter().copied().collect();
+        self.food.clear();
+        for coord in food {
+            self.refresh(coord);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.clear_field();
+        self.players.clear();
+        self.entrants.clear();
+    }
+
+    fn alive(&self) -> usize {
+        self.snakes.values().filter(|snake| snake.state == SnakeState::Alive).count()
+    }
+
+    pub fn team_of(&self, id: i32) -> Option<u32> {
+        self.players.get(&id).and_then(|player| player.team)
+    }
+
+    fn teammates(&self, id: i32, other: i32) -> bool {
+        self.team_of(id).is_some() && self.team_of(id) == self.team_of(other)
+    }
+
+    /// Teams or lone snakes that still have an alive snake.
+    fn sides(&self) -> usize {
+        let sides: HashSet<(Option<u32>, i32)> = self.snakes.values()
+            .filter(|snake| snake.state == SnakeState::Alive)
+            .map(|snake| match self.team_of(snake.id) {
+                Some(team) => (Some(team), 0),
+                None => (None, snake.id),
+            })
+            .collect();
+        sides.len()
+    }
+
+    /// The requested team if it exists, otherwise the one with the fewest players.
+    pub fn pick_team(&self, requested: Option<u32>) -> Option<u32> {
+        if self.config.teams == 0 {
+            return None;
+        }
+        if let Some(team) = requested.filter(|team| (1..=self.config.teams).contains(team)) {
+            return Some(team);
+        }
+        (1..=self.config.teams).min_by_key(|team| {
+            self.players.values().filter(|player| player.team == Some(*team)).count()
+        })
+    }
+
+    /// Sum of the members' scores for every team, including empty ones.
+    pub fn team_scores(&self) -> BTreeMap<u32, i32> {
+        let mut scores: BTreeMap<u32, i32> = (1..=self.config.teams).map(|team| (team, 0)).collect();
+        for player in self.players.values() {
+            if let Some(score) = player.team.and_then(|team| scores.get_mut(&team)) {
+                *score += player.score;
+            }
+        }
+        scores
+    }
+
+    /// Time left in a timed round.
+    pub fn time_left(&self) -> Option<Duration> {
+        let ticks = self.config.round_ticks()?;
+        let left = (ticks - (self.order - self.round_start)).max(0);
+        Some(self.config.state_delay * left as u32)
+    }
+
+    pub fn round_over(&self) -> bool {
+        match self.config.mode {
+            Mode::Endless => false,
+            Mode::Timed(_) => self.time_left().is_some_and(|left| left.is_zero()),
+            Mode::FirstTo(score) if self.config.teams > 0 => self.team_scores().values().any(|team| *team >= score),
+            Mode::FirstTo(score) => self.players.values().any(|player| player.score >= score),
+            Mode::LastStanding => self.contested && self.sides() <= 1,
+        }
+    }
+
+    /// A round under way takes no new snakes; a last standing round is under way once it is contested.
+    pub fn mid_round(&self) -> bool {
+        match self.config.mode {
+            Mode::Endless => false,
+            Mode::LastStanding => self.contested,
+            _ => true,
+        }
+    }
+
+    /// Gives the player a snake when the next round starts.
+    pub fn enter(&mut self, id: i32) {
+        self.entrants.insert(id);
+    }
+
+    pub fn summary(&self) -> RoundSummary {
+        let mut standings: Vec<Player> = self.players.values().cloned().collect();
+        standings.sort_by_key(|player| std::cmp::Reverse(player.score));
+        let survivor = match self.config.mode {
+            Mode::LastStanding => self.snakes.values().find(|snake| snake.state == SnakeState::Alive).map(|snake| snake.id),
+            _ => None,
+        };
+        let leader = match standings.as_slice() {
+            [first, second, ..] if first.score == second.score => None,
+            [first, ..] => Some(first.id),
+            [] => None,
+        };
+        let winner = survivor.or(leader);
+        let mut teams: Vec<(u32, i32)> = self.team_scores().into_iter().collect();
+        teams.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+        let winning_team = match (self.config.mode, teams.as_slice()) {
+            (Mode::LastStanding, _) => survivor.and_then(|id| self.team_of(id)),
+            (_, [first, second, ..]) if first.1 == second.1 => None,
+            (_, [first, ..]) => Some(first.0),
+            _ => None,
+        };
+        RoundSummary { round: self.round, winner, standings, winning_team, teams }
+    }
+
+    /// Ends the round: clears the field, resets scores and respawns everyone who played in it.
+    /// Returns the ids that got a new snake.
+    pub fn restart(&mut self) -> Vec<i32> {
+        self.clear_field();
+        for player in self.players.values_mut() {
+            player.score = 0;
+        }
+        let mut entrants = std::mem::take(&mut self.entrants);
+        entrants.retain(|id| self.players.contains_key(id));
+        let mut spawned = Vec::new();
+        for id in entrants {
+            if self.spawn_snake(id).is_ok() {
+                spawned.push(id);
+            }
+        }
+        self.round += 1;
+        self.round_start = self.order;
+        self.contested = false;
+        spawned
+    }
+
+    pub fn tick(&mut self) -> Tick {
+        self.contested |= self.sides() > 1;
+        // first move every snake, so the outcome doesn't depend on iteration order
+        let mut heads = BTreeMap::<i32, Coord>::new();
+        let mut fed = HashSet::<i32>::new();
+        let mut vacated = Vec::new();
+        for (id, snake) in self.snakes.iter_mut() {
+            if let Some(head) = snake.head() {
+                // without a new head the snake stays in place and is buried with the other dead ones
+                let Some(new_coord) = self.config.step(head, snake.head_direction) else {
+                    continue;
+                };
+                if self.world[new_coord.y as usize][new_coord.x as usize] == WorldCell::Obstacle {
+                    continue;
                 }
-                let size_y = self.config.height as i32;
-                let size_x = self.config.width as i32;
-                new_y = (new_y + size_y) % size_y;
-                new_x = (new_x + size_x) % size_x;
-
-                let new_coord = Coord::new(new_x, new_y);
-
-                let new_y = new_y as usize;
-                let new_x = new_x as usize;
-
-                match world[new_y][new_x] {
-                    WorldCell::Empty => {
-                        snake.body.push(new_coord);
-                        let tail = snake.body.remove(0);
-                        new_world[tail.y as usize][tail.x as usize] = WorldCell::Empty;
-                        new_world[new_y][new_x] = WorldCell::Snake;
-                        return true;
-                    }
-                    WorldCell::Food => {
-                        snake.body.push(new_coord);
-                        new_world[new_y][new_x] = WorldCell::Snake;
-                        self.food.remove(self.food.iter().position(|x| x == &new_coord).expect("food coord"));
-                        return true;
-                    }
-                    WorldCell::Snake => {
-                        let mut rng = self.rng.take();
-                        for coord in snake.body.drain(..) {
-                            if rng.gen_bool(0.5f64) {
-                                // self.add_food_to(coord);
-                                new_world[coord.y as usize][coord.x as usize] = WorldCell::Food;
-                                self.food.push(coord);
-                            } else {
-                                // self.clear_coord(coord);
-                                new_world[coord.y as usize][coord.x as usize] = WorldCell::Empty;
-                            }
-                        }
-                        self.rng.set(rng);
-                        return false;
+
+                snake.body.push_back(new_coord);
+                if self.food.contains(new_coord) {
+                    fed.insert(*id);
+                } else if let Some(tail) = snake.body.pop_front() {
+                    vacated.push((*id, tail));
+                }
+                heads.insert(*id, new_coord);
+            }
+        }
+        for (id, tail) in vacated {
+            self.vacate(tail, id);
+        }
+
+        // then resolve all collisions at once against the moved bodies
+        let mut head_cells = HashMap::<Coord, Vec<i32>>::new();
+        for (id, head) in heads.iter() {
+            head_cells.entry(*head).or_default().push(*id);
+        }
+        let mut hit_by = HashMap::<i32, HashSet<i32>>::new();
+        let mut bit_itself = HashSet::<i32>::new();
+        for (id, head) in heads.iter() {
+            let body_owners = &self.occupancy[head.y as usize][head.x as usize].owners;
+            if body_owners.contains(id) {
+                bit_itself.insert(*id);
+            }
+            let owners = hit_by.entry(*id).or_default();
+            owners.extend(body_owners.iter().filter(|owner| *owner != id));
+            owners.extend(head_cells[head].iter().filter(|other| *other != id));
+        }
+        for (id, head) in heads.iter() {
+            self.occupy(*head, *id);
+        }
+        let mut dead: Vec<i32> = self.snakes.keys().filter(|id| !heads.contains_key(id)).copied().collect();
+        for (id, head) in heads.iter() {
+            if self.occupancy[head.y as usize][head.x as usize].count() > 1 {
+                let owners = hit_by.remove(id).unwrap_or_default();
+                let passes = !self.config.friendly_fire
+                    && !bit_itself.contains(id)
+                    && owners.iter().all(|owner| self.teammates(*id, *owner));
+                if passes {
+                    continue;
+                }
+                dead.push(*id);
+                let enemies: Vec<i32> = owners.into_iter().filter(|owner| !self.teammates(*id, *owner)).collect();
+                for owner in enemies {
+                    if let Some(player) = self.players.get_mut(&owner) {
+                        player.score += KILL_SCORE;
                     }
                 }
-            } else { false }
-        });
+            }
+        }
+
+        let mut eaten = Vec::<(i32, Coord)>::new();
+        for (id, head) in heads.iter().filter(|(id, _)| fed.contains(id) && !dead.contains(id)) {
+            eaten.push((*id, *head));
+            self.food.remove(*head);
+            if let Some(player) = self.players.get_mut(id) {
+                player.score += FOOD_SCORE;
+            }
+        }
+
+        let mut remains = Vec::new();
+        for id in dead.iter() {
+            if let Some(snake) = self.snakes.remove(id) {
+                for coord in snake.body {
+                    self.vacate(coord, snake.id);
+                    remains.push(coord);
+                }
+            }
+        }
+        for coord in remains {
+            let taken = self.occupancy(coord).count() > 0;
+            if !taken && !self.food.contains(coord) && self.rng.gen_bool(0.5f64) {
+                self.add_food(coord);
+            }
+        }
 
-        let mut rng = self.rng.take();
-        for _ in 0..(self.food.len() - self.config.food_count) {
-            let coord = new_world.get_free_random_coord(&mut rng);
-            self.food.push(coord);
-            new_world[coord.y as usize][coord.y as usize] = WorldCell::Food;
+        let spawners: Vec<Coord> = self.config.map.iter().flat_map(|map| map.food.iter().copied()).collect();
+        for coord in spawners {
+            if self.world[coord.y as usize][coord.x as usize] == WorldCell::Empty {
+                self.add_food(coord);
+            }
         }
-        self.rng.set(rng);
+        for _ in 0..self.food_target().saturating_sub(self.food.len()) {
+            let Some(coord) = self.get_free_random_coord() else {
+                break;
+            };
+            self.add_food(coord);
+        }
+
+        self.order += 1;
+
+        Tick { dead, eaten }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(teams: u32) -> Game {
+        let config = Config {
+            width: 10,
+            height: 10,
+            food_count: 0,
+            food_per_player: 0.0,
+            state_delay: Duration::from_millis(100),
+            walled: false,
+            map: None,
+            mode: Mode::Endless,
+            teams,
+            friendly_fire: false,
+            max_players: None,
+        };
+        Game::new(config, 0)
+    }
+
+    /// `body` goes from tail to head.
+    fn add(game: &mut Game, id: i32, team: Option<u32>, body: &[(i32, i32)], direction: Direction) {
+        let mut player = Player::new(id, id.to_string(), Role::Normal, PlayerKind::Human);
+        player.team = team;
+        game.players.insert(id, player);
+        game.add_snake(Snake {
+            head_direction: direction,
+            body: body.iter().map(|(x, y)| Coord::new(*x, *y)).collect(),
+            id,
+            state: SnakeState::Alive,
+        });
+    }
+
+    #[test]
+    fn long_timed_round_does_not_overflow() {
+        let mut game = game(0);
+        game.config.mode = Mode::Timed(u32::MAX);
+        assert_eq!(game.config.round_ticks(), Some(i32::MAX));
+        assert!(game.time_left().is_some_and(|left| !left.is_zero()));
+    }
+
+    #[test]
+    fn late_entrant_waits_for_next_round() {
+        let mut game = game(0);
+        game.config.mode = Mode::LastStanding;
+        add(&mut game, 1, None, &[(1, 1), (2, 1)], Direction::RIGHT);
+        assert!(!game.mid_round());
+        add(&mut game, 2, None, &[(1, 6), (2, 6)], Direction::RIGHT);
+        game.tick();
+        assert!(game.mid_round());
+
+        game.players.insert(3, Player::new(3, "3".to_owned(), Role::Normal, PlayerKind::Human));
+        game.enter(3);
+        assert!(!game.snakes.contains_key(&3));
+        let mut spawned = game.restart();
+        spawned.sort();
+        assert_eq!(spawned, vec![1, 2, 3]);
+        assert!(!game.mid_round());
+    }
+
+    #[test]
+    fn head_on_kills_both() {
+        let mut game = game(0);
+        add(&mut game, 1, None, &[(2, 5), (3, 5)], Direction::RIGHT);
+        add(&mut game, 2, None, &[(6, 5), (5, 5)], Direction::LEFT);
+
+        assert_eq!(game.tick().dead, vec![1, 2]);
+        assert!(game.snakes.is_empty());
+    }
+
+    #[test]
+    fn tail_leaving_this_tick_is_free() {
+        let mut game = game(0);
+        add(&mut game, 1, None, &[(3, 3), (4, 3), (4, 4), (3, 4)], Direction::UP);
+        add(&mut game, 2, None, &[(6, 2), (7, 2)], Direction::RIGHT);
+        add(&mut game, 3, None, &[(5, 3), (6, 3)], Direction::UP);
+
+        assert!(game.tick().dead.is_empty());
+        assert_eq!(game.snakes[&1].head(), Some(Coord::new(3, 3)));
+        assert_eq!(game.snakes[&3].head(), Some(Coord::new(6, 2)));
+    }
+
+    #[test]
+    fn kill_goes_to_body_owner() {
+        let mut game = game(0);
+        add(&mut game, 1, None, &[(5, 2), (5, 3), (5, 4), (5, 5), (5, 6)], Direction::DOWN);
+        add(&mut game, 2, None, &[(3, 5), (4, 5)], Direction::RIGHT);
+        add(&mut game, 3, None, &[(8, 5), (7, 5)], Direction::LEFT);
 
-        world.copy(&new_world);
-    }    
+        assert_eq!(game.tick().dead, vec![2]);
+        // the cell still holds a body after the dead snake is removed, the credit must not follow the dead one
+        assert_eq!(game.tick().dead, vec![3]);
+        assert_eq!(game.players[&1].score, 2 * KILL_SCORE);
+        assert_eq!(game.players[&2].score, 0);
+    }
+
+    #[test]
+    fn teammate_does_not_cover_own_body() {
+        let mut game = game(2);
+        add(&mut game, 1, Some(1), &[(3, 3), (4, 3), (5, 3), (5, 4), (4, 4)], Direction::UP);
+        add(&mut game, 2, Some(1), &[(4, 2), (4, 3)], Direction::RIGHT);
+
+        assert_eq!(game.tick().dead, vec![1]);
+        assert!(game.snakes.contains_key(&2));
+    }
 }
 
 }
 
 pub mod protobuf {
 
-    use crate::snakes::snakes;
+    use crate::snakes::{snakes, keypoints, ext};
+    use crate::snakes::snakes::game_state::snake::SnakeState;
+    use crate::new::map::Map;
     use super::game;
-    // use std::vec;
+    use std::time::Duration;
+    use anyhow::Result;
 
     impl<'a> From<&'a game::Coord> for snakes::game_state::Coord {
         fn from(value: &game::Coord) -> Self {
@@ -215,13 +841,79 @@ pub mod protobuf {
         }
     }
 
+    impl From<game::Direction> for snakes::Direction {
+        fn from(value: game::Direction) -> Self {
+            match value {
+                game::Direction::UP => snakes::Direction::UP,
+                game::Direction::DOWN => snakes::Direction::DOWN,
+                game::Direction::LEFT => snakes::Direction::LEFT,
+                game::Direction::RIGHT => snakes::Direction::RIGHT,
+            }
+        }
+    }
+
+    impl From<snakes::Direction> for game::Direction {
+        fn from(value: snakes::Direction) -> Self {
+            match value {
+                snakes::Direction::UP => game::Direction::UP,
+                snakes::Direction::DOWN => game::Direction::DOWN,
+                snakes::Direction::LEFT => game::Direction::LEFT,
+                snakes::Direction::RIGHT => game::Direction::RIGHT,
+            }
+        }
+    }
+
+    impl From<game::Role> for snakes::NodeRole {
+        fn from(value: game::Role) -> Self {
+            match value {
+                game::Role::Normal => snakes::NodeRole::NORMAL,
+                game::Role::Master => snakes::NodeRole::MASTER,
+                game::Role::Deputy => snakes::NodeRole::DEPUTY,
+                game::Role::Viewer => snakes::NodeRole::VIEWER,
+            }
+        }
+    }
+
+    impl From<snakes::NodeRole> for game::Role {
+        fn from(value: snakes::NodeRole) -> Self {
+            match value {
+                snakes::NodeRole::NORMAL => game::Role::Normal,
+                snakes::NodeRole::MASTER => game::Role::Master,
+                snakes::NodeRole::DEPUTY => game::Role::Deputy,
+                snakes::NodeRole::VIEWER => game::Role::Viewer,
+            }
+        }
+    }
+
+    impl From<game::PlayerKind> for snakes::PlayerType {
+        fn from(value: game::PlayerKind) -> Self {
+            match value {
+                game::PlayerKind::Human => snakes::PlayerType::HUMAN,
+                game::PlayerKind::Robot => snakes::PlayerType::ROBOT,
+            }
+        }
+    }
+
+    impl From<snakes::PlayerType> for game::PlayerKind {
+        fn from(value: snakes::PlayerType) -> Self {
+            match value {
+                snakes::PlayerType::HUMAN => game::PlayerKind::Human,
+                snakes::PlayerType::ROBOT => game::PlayerKind::Robot,
+            }
+        }
+    }
+
     impl<'a> From<&'a game::Snake> for snakes::game_state::Snake {
         fn from(value: &'a game::Snake) -> Self {
             let mut proto_snake = snakes::game_state::Snake::new();
             proto_snake.set_player_id(value.id);
-            proto_snake.points = value.body.iter().collect();
+            proto_snake.points = keypoints::encode(value.body.iter().rev().map(|coord| (coord.x, coord.y)));
+            proto_snake.set_head_direction(value.head_direction.into());
+            proto_snake.set_state(match value.state {
+                game::SnakeState::Alive => SnakeState::ALIVE,
+                game::SnakeState::Zombie => SnakeState::ZOMBIE,
+            });
             proto_snake
-            
         }
     }
 
@@ -235,4 +927,143 @@ pub mod protobuf {
         }
     }
 
+    /// Expands key points back into body cells; needs the field size to wrap offsets across edges.
+    pub fn snake_from_proto(value: &snakes::game_state::Snake, width: usize, height: usize) -> Result<game::Snake> {
+        Ok(game::Snake {
+            head_direction: value.head_direction().into(),
+            body: keypoints::decode(&value.points, width as i32, height as i32)?
+                .into_iter()
+                .rev()
+                .map(|(x, y)| game::Coord::new(x, y))
+                .collect(),
+            id: value.player_id(),
+            state: match value.state() {
+                SnakeState::ALIVE => game::SnakeState::Alive,
+                SnakeState::ZOMBIE => game::SnakeState::Zombie,
+            },
+        })
+    }
+
+    /// Addresses are not part of the engine, the caller fills ip_address and port.
+    impl<'a> From<&'a game::Player> for snakes::GamePlayer {
+        fn from(value: &'a game::Player) -> Self {
+            let mut player = snakes::GamePlayer::new();
+            player.set_id(value.id);
+            player.set_name(value.name.clone());
+            player.set_score(value.score);
+            player.set_role(value.role.into());
+            player.set_type(value.kind.into());
+            ext::set_team(&mut player, value.team);
+            player
+        }
+    }
+
+    impl<'a> From<&'a snakes::GamePlayer> for game::Player {
+        fn from(value: &'a snakes::GamePlayer) -> Self {
+            game::Player {
+                id: value.id(),
+                name: value.name().to_owned(),
+                score: value.score(),
+                role: value.role().into(),
+                kind: value.type_().into(),
+                team: ext::team(value),
+            }
+        }
+    }
+
+    impl<'a> From<&'a game::Config> for snakes::GameConfig {
+        fn from(value: &'a game::Config) -> Self {
+            let mut config = snakes::GameConfig::new();
+            config.set_width(value.width as i32);
+            config.set_height(value.height as i32);
+            config.set_food_static(value.food_count as i32);
+            config.set_state_delay_ms(value.state_delay.as_millis() as i32);
+            ext::set_walled(&mut config, value.walled);
+            ext::set_map(&mut config, value.map.as_ref().map(|map| map.to_bytes()));
+            let (kind, goal) = match value.mode {
+                game::Mode::Endless => (0, 0),
+                game::Mode::Timed(seconds) => (1, seconds as u64),
+                game::Mode::FirstTo(score) => (2, score as u64),
+                game::Mode::LastStanding => (3, 0),
+            };
+            ext::set_mode(&mut config, kind, goal);
+            ext::set_teams(&mut config, value.teams, value.friendly_fire);
+            ext::set_food_per_player(&mut config, value.food_per_player);
+            ext::set_max_players(&mut config, value.max_players);
+            config
+        }
+    }
+
+    /// Expects a config that already passed `validate::check`.
+    impl<'a> From<&'a snakes::GameConfig> for game::Config {
+        fn from(value: &'a snakes::GameConfig) -> Self {
+            game::Config {
+                width: value.width() as usize,
+                height: value.height() as usize,
+                food_count: value.food_static() as usize,
+                food_per_player: ext::food_per_player(value),
+                state_delay: Duration::from_millis(value.state_delay_ms() as u64),
+                walled: ext::walled(value),
+                map: ext::map(value).and_then(|bytes| {
+                    Map::from_bytes(bytes, value.width() as usize, value.height() as usize).ok()
+                }),
+                mode: mode_from_proto(value),
+                teams: ext::teams(value).min(game::MAX_TEAMS),
+                friendly_fire: ext::friendly_fire(value),
+                max_players: ext::max_players(value),
+            }
+        }
+    }
+
+    /// Unknown kinds fall back to endless, a zero goal is raised to 1.
+    pub fn mode_from_proto(config: &snakes::GameConfig) -> game::Mode {
+        match ext::mode(config) {
+            (1, seconds) => game::Mode::Timed(seconds.clamp(1, u32::MAX as u64) as u32),
+            (2, score) => game::Mode::FirstTo(score.clamp(1, i32::MAX as u64) as i32),
+            (3, _) => game::Mode::LastStanding,
+            _ => game::Mode::Endless,
+        }
+    }
+
+    pub fn state(game: &game::Game) -> snakes::GameState {
+        let mut state = snakes::GameState::new();
+        state.set_state_order(game.order);
+        ext::set_round(&mut state, game.round, game.round_start);
+        ext::set_seed(&mut state, game.seed());
+        state.snakes = game.snakes.values().collect();
+        state.foods = game.food.iter().collect();
+        let mut players = snakes::GamePlayers::new();
+        players.players = game.players.values().map(|player| player.into()).collect();
+        state.players = Some(players).into();
+        state
+    }
+
+    /// Leaves the game untouched when a snake can't be decoded.
+    pub fn apply_state(game: &mut game::Game, state: &snakes::GameState) -> Result<()> {
+        let width = game.config.width;
+        let height = game.config.height;
+        let snakes = state.snakes.iter()
+            .map(|snake| snake_from_proto(snake, width, height))
+            .collect::<Result<Vec<_>>>()?;
+        game.clear();
+        game.order = state.state_order();
+        if let Some((round, round_start)) = ext::round(state) {
+            game.round = round;
+            game.round_start = round_start;
+        }
+        if let Some(seed) = ext::seed(state) {
+            game.reseed(seed);
+        }
+        for snake in snakes {
+            game.add_snake(snake);
+        }
+        for coord in state.foods.iter() {
+            game.add_food(game::Coord::new(coord.x().rem_euclid(width as i32), coord.y().rem_euclid(height as i32)));
+        }
+        for player in state.players.players.iter() {
+            game.players.insert(player.id(), player.into());
+        }
+        Ok(())
+    }
+
 }
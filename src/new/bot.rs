@@ -0,0 +1,281 @@
+use std::collections::{HashSet, VecDeque};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use super::base::game::{Coord, Direction, Game, Snake, WorldCell};
+
+const DIRECTIONS: [Direction; 4] = [Direction::UP, Direction::DOWN, Direction::LEFT, Direction::RIGHT];
+
+pub const STRATEGIES: [&str; 4] = ["random", "greedy", "bfs", "flood"];
+
+/// Used for robots whose strategy isn't known, e.g. ones adopted from a master that didn't send it.
+pub const DEFAULT_STRATEGY: &str = "flood";
+
+/// Read-only view of the game from one snake's point of view.
+pub struct View<'a> {
+    pub game: &'a Game,
+    pub id: i32,
+}
+
+impl<'a> View<'a> {
+    pub fn new(game: &'a Game, id: i32) -> Self {
+        View { game, id }
+    }
+
+    pub fn snake(&self) -> Option<&'a Snake> {
+        self.game.snakes.get(&self.id)
+    }
+
+    pub fn head(&self) -> Option<Coord> {
+        self.snake()?.head()
+    }
+
+    /// Direction the snake moved in last, the one it keeps if nobody steers.
+    pub fn direction(&self) -> Option<Direction> {
+        Some(self.snake()?.head_direction)
+    }
+
+    /// Cells a head can move into without dying this tick.
+    pub fn passable(&self, coord: Coord) -> bool {
+        matches!(self.game.world[coord.y as usize][coord.x as usize], WorldCell::Empty | WorldCell::Food)
+    }
+
+    /// Moves allowed by the master (no reversing into the neck) that don't hit anything.
+    pub fn safe_moves(&self) -> Vec<(Direction, Coord)> {
+        let (Some(head), Some(current)) = (self.head(), self.direction()) else {
+            return Vec::new();
+        };
+        DIRECTIONS.into_iter()
+            .filter(|direction| *direction != current.opposite())
+            .filter_map(|direction| Some((direction, self.game.config.step(head, direction)?)))
+            .filter(|(_, next)| self.passable(*next))
+            .collect()
+    }
+
+    /// Manhattan distance, measured across the edges unless the field is walled.
+    pub fn distance(&self, a: Coord, b: Coord) -> i32 {
+        let axis = |a: i32, b: i32, size: usize| {
+            let d = (a - b).abs();
+            if self.game.config.walled { d } else { d.min(size as i32 - d) }
+        };
+        axis(a.x, b.x, self.game.config.width) + axis(a.y, b.y, self.game.config.height)
+    }
+
+    pub fn nearest_food(&self, from: Coord) -> Option<Coord> {
+        self.game.food.iter().copied().min_by_key(|food| self.distance(from, *food))
+    }
+
+    /// Number of passable cells reachable from `start`, capped at `limit`.
+    pub fn space(&self, start: Coord, limit: usize) -> usize {
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(coord) = queue.pop_front() {
+            if seen.len() >= limit {
+                break;
+            }
+            for direction in DIRECTIONS {
+                if let Some(next) = self.game.config.step(coord, direction) {
+                    if self.passable(next) && seen.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        seen.len().min(limit)
+    }
+
+    /// First move of a shortest path to any food that avoids every body, walls and obstacles.
+    pub fn path_to_food(&self) -> Option<Direction> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        for (direction, next) in self.safe_moves() {
+            if seen.insert(next) {
+                queue.push_back((next, direction));
+            }
+        }
+        while let Some((coord, first)) = queue.pop_front() {
+            if self.game.food.contains(coord) {
+                return Some(first);
+            }
+            for direction in DIRECTIONS {
+                if let Some(next) = self.game.config.step(coord, direction) {
+                    if self.passable(next) && seen.insert(next) {
+                        queue.push_back((next, first));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Steers one snake; called once per tick with the state the master is about to advance.
+pub trait BotStrategy {
+    fn name(&self) -> &'static str;
+
+    fn steer(&mut self, view: &View) -> Direction;
+}
+
+/// Builds one of `STRATEGIES`, the seed only matters for the ones that use randomness.
+pub fn by_name(name: &str, seed: u64) -> Option<Box<dyn BotStrategy>> {
+    match name {
+        "random" => Some(Box::new(RandomSafe::new(seed))),
+        "greedy" => Some(Box::new(Greedy)),
+        "bfs" => Some(Box::new(Pathfinder)),
+        "flood" => Some(Box::new(FloodFill)),
+        _ => None,
+    }
+}
+
+fn straight_or_first(view: &View, moves: &[(Direction, Coord)]) -> Direction {
+    let current = view.direction().unwrap_or(Direction::UP);
+    if moves.iter().any(|(direction, _)| *direction == current) {
+        return current;
+    }
+    moves.first().map_or(current, |(direction, _)| *direction)
+}
+
+/// Keeps going while the next cell is safe, otherwise turns to a random safe one.
+pub struct RandomSafe {
+    rng: StdRng,
+}
+
+impl RandomSafe {
+    pub fn new(seed: u64) -> Self {
+        RandomSafe { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl BotStrategy for RandomSafe {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn steer(&mut self, view: &View) -> Direction {
+        let current = view.direction().unwrap_or(Direction::UP);
+        let moves = view.safe_moves();
+        if moves.iter().any(|(direction, _)| *direction == current) {
+            return current;
+        }
+        moves.choose(&mut self.rng).map_or(current, |(direction, _)| *direction)
+    }
+}
+
+/// Takes the safe move that gets closest to the nearest food, ignoring what is in between.
+pub struct Greedy;
+
+impl BotStrategy for Greedy {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn steer(&mut self, view: &View) -> Direction {
+        let moves = view.safe_moves();
+        let Some(food) = view.head().and_then(|head| view.nearest_food(head)) else {
+            return straight_or_first(view, &moves);
+        };
+        moves.iter()
+            .min_by_key(|(_, next)| view.distance(*next, food))
+            .map_or_else(|| straight_or_first(view, &moves), |(direction, _)| *direction)
+    }
+}
+
+/// Follows a shortest path around bodies to the nearest reachable food.
+pub struct Pathfinder;
+
+impl BotStrategy for Pathfinder {
+    fn name(&self) -> &'static str {
+        "bfs"
+    }
+
+    fn steer(&mut self, view: &View) -> Direction {
+        view.path_to_food().unwrap_or_else(|| straight_or_first(view, &view.safe_moves()))
+    }
+}
+
+/// Prefers the move with the most room left, then the shortest way to food.
+/// Room is counted up to twice the snake's length, more than that is enough to get out.
+pub struct FloodFill;
+
+impl BotStrategy for FloodFill {
+    fn name(&self) -> &'static str {
+        "flood"
+    }
+
+    fn steer(&mut self, view: &View) -> Direction {
+        let moves = view.safe_moves();
+        let length = view.snake().map_or(1, |snake| snake.body.len());
+        let limit = length * 2 + 1;
+        let best = moves.iter().map(|(_, next)| view.space(*next, limit)).max().unwrap_or(0);
+        let roomy: Vec<(Direction, Coord)> = moves.iter()
+            .copied()
+            .filter(|(_, next)| view.space(*next, limit) == best)
+            .collect();
+        match view.path_to_food().filter(|path| roomy.iter().any(|(direction, _)| direction == path)) {
+            Some(path) => path,
+            None => straight_or_first(view, &roomy),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::new::base::game::{Config, Mode, SnakeState};
+
+    /// `body` goes from tail to head.
+    fn add(game: &mut Game, id: i32, body: &[(i32, i32)], direction: Direction) {
+        game.add_snake(Snake {
+            head_direction: direction,
+            body: body.iter().map(|(x, y)| Coord::new(*x, *y)).collect(),
+            id,
+            state: SnakeState::Alive,
+        });
+    }
+
+    /// Snake 1 heads right into the body of snake 2, with food on the other side of it.
+    fn blocked(up_too: bool) -> Game {
+        let config = Config {
+            width: 10,
+            height: 10,
+            food_count: 0,
+            food_per_player: 0.0,
+            state_delay: Duration::from_millis(100),
+            walled: false,
+            map: None,
+            mode: Mode::Endless,
+            teams: 0,
+            friendly_fire: false,
+            max_players: None,
+        };
+        let mut game = Game::new(config, 0);
+        add(&mut game, 1, &[(2, 5), (3, 5)], Direction::RIGHT);
+        add(&mut game, 2, &[(4, 3), (4, 4), (4, 5), (4, 6), (4, 7)], Direction::DOWN);
+        if up_too {
+            add(&mut game, 3, &[(3, 4), (2, 4)], Direction::LEFT);
+        }
+        game.add_food(Coord::new(6, 5));
+        game
+    }
+
+    #[test]
+    fn strategies_only_take_safe_moves() {
+        for up_too in [false, true] {
+            let game = blocked(up_too);
+            let view = View::new(&game, 1);
+            let safe: Vec<Direction> = view.safe_moves().into_iter().map(|(direction, _)| direction).collect();
+            assert!(!safe.is_empty());
+            for name in STRATEGIES {
+                for seed in 0..10 {
+                    let mut strategy = by_name(name, seed).unwrap();
+                    let direction = strategy.steer(&view);
+                    assert!(safe.contains(&direction), "{} steered {:?}, safe moves are {:?}", name, direction, safe);
+                }
+            }
+        }
+    }
+}
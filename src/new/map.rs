@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow, bail};
+use serde_json::Value;
+
+use super::base::game::Coord;
+
+const EMPTY: u64 = 0;
+const WALL: u64 = 1;
+const SPAWN: u64 = 2;
+const FOOD: u64 = 3;
+
+/// Static layout of a field: lethal walls, cells where snakes may spawn
+/// (anywhere if empty) and cells that always grow food back.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Map {
+    pub width: usize,
+    pub height: usize,
+    pub walls: Vec<Coord>,
+    pub spawns: Vec<Coord>,
+    pub food: Vec<Coord>,
+}
+
+impl Map {
+    /// Reads `.json` files as JSON and everything else as a text grid.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json(&text)
+        } else {
+            Self::from_text(&text)
+        }
+    }
+
+    /// One line per row: `#` is a wall, `S` a spawn cell, `@` a food spawner, anything else is empty.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let rows: Vec<&str> = text.lines().map(|line| line.trim_end()).collect();
+        let height = rows.iter().rposition(|row| !row.is_empty()).map_or(0, |last| last + 1);
+        let mut map = Map {
+            width: rows.iter().map(|row| row.chars().count()).max().unwrap_or(0),
+            height,
+            ..Default::default()
+        };
+        for (y, row) in rows[..height].iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                let coord = Coord::new(x as i32, y as i32);
+                match cell {
+                    '#' => map.walls.push(coord),
+                    'S' => map.spawns.push(coord),
+                    '@' => map.food.push(coord),
+                    _ => {}
+                }
+            }
+        }
+        map.check()?;
+        Ok(map)
+    }
+
+    /// `{"width": 20, "height": 20, "walls": [[x, y], ...], "spawns": [...], "food": [...]}`
+    pub fn from_json(text: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(text)?;
+        let size = |key: &str| {
+            value.get(key)
+                .and_then(Value::as_u64)
+                .map(|size| size as usize)
+                .ok_or_else(|| anyhow!("map has no {}", key))
+        };
+        let cells = |key: &str| -> Result<Vec<Coord>> {
+            let Some(cells) = value.get(key) else {
+                return Ok(Vec::new());
+            };
+            cells.as_array()
+                .ok_or_else(|| anyhow!("map {} must be a list of [x, y]", key))?
+                .iter()
+                .map(|cell| match cell.as_array().map(|xy| xy.as_slice()) {
+                    Some([x, y]) => match (x.as_i64(), y.as_i64()) {
+                        (Some(x), Some(y)) => Ok(Coord::new(x as i32, y as i32)),
+                        _ => bail!("map {} has a non integer coordinate", key),
+                    },
+                    _ => bail!("map {} must be a list of [x, y]", key),
+                })
+                .collect()
+        };
+        let map = Map {
+            width: size("width")?,
+            height: size("height")?,
+            walls: cells("walls")?,
+            spawns: cells("spawns")?,
+            food: cells("food")?,
+        };
+        map.check()?;
+        Ok(map)
+    }
+
+    fn check(&self) -> Result<()> {
+        let inside = |coord: &Coord| {
+            (0..self.width as i32).contains(&coord.x) && (0..self.height as i32).contains(&coord.y)
+        };
+        if let Some(coord) = self.walls.iter().chain(&self.spawns).chain(&self.food).find(|coord| !inside(coord)) {
+            bail!("map cell {},{} is outside of {}x{}", coord.x, coord.y, self.width, self.height);
+        }
+        Ok(())
+    }
+
+    /// Run-length encoded cells in row order, small enough for the config extension field.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut cells = vec![EMPTY; self.width * self.height];
+        for (kind, coords) in [(WALL, &self.walls), (SPAWN, &self.spawns), (FOOD, &self.food)] {
+            for coord in coords {
+                cells[coord.y as usize * self.width + coord.x as usize] = kind;
+            }
+        }
+        let mut bytes = Vec::new();
+        let mut cells = cells.into_iter().peekable();
+        while let Some(kind) = cells.next() {
+            let mut run = 1;
+            while cells.next_if_eq(&kind).is_some() {
+                run += 1;
+            }
+            write_varint(&mut bytes, kind);
+            write_varint(&mut bytes, run);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8], width: usize, height: usize) -> Result<Self> {
+        let mut map = Map { width, height, ..Default::default() };
+        let mut bytes = bytes.iter().copied();
+        let mut cell = 0usize;
+        while let Some(kind) = read_varint(&mut bytes) {
+            let run = read_varint(&mut bytes).ok_or_else(|| anyhow!("map run without length"))? as usize;
+            let Some(end) = cell.checked_add(run).filter(|end| *end <= width * height) else {
+                bail!("map is larger than {}x{}", width, height);
+            };
+            for i in cell..end {
+                let coord = Coord::new((i % width) as i32, (i / width) as i32);
+                match kind {
+                    WALL => map.walls.push(coord),
+                    SPAWN => map.spawns.push(coord),
+                    FOOD => map.food.push(coord),
+                    _ => {}
+                }
+            }
+            cell = end;
+        }
+        Ok(map)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint<I: Iterator<Item = u8>>(bytes: &mut I) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = bytes.next()?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes_rejects_oversized_runs() {
+        let mut bytes = vec![1, 1];
+        write_varint(&mut bytes, 1);
+        write_varint(&mut bytes, u64::MAX);
+        assert!(Map::from_bytes(&bytes, 10, 10).is_err());
+    }
+}
@@ -1,2 +1,5 @@
 pub mod base;
+pub mod bot;
+pub mod map;
+pub mod spawn;
 pub mod sockets;
\ No newline at end of file
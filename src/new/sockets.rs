@@ -1,4 +1,4 @@
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::net::{Ipv4Addr, UdpSocket};
 
 
 pub fn create_sockets() -> (UdpSocket, UdpSocket) {
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use super::base::game::{Coord, Direction, WorldCell};
+
+const RADIUS: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoSpace;
+
+impl Display for NoSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no available space for snake")
+    }
+}
+
+impl std::error::Error for NoSpace {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Spawn {
+    pub head: Coord,
+    pub tail: Coord,
+    pub direction: Direction,
+}
+
+fn wrap(value: i32, size: usize) -> usize {
+    value.rem_euclid(size as i32) as usize
+}
+
+/// Marks the centers of 5x5 squares without snake cells; squares wrap across the edges like the field does.
+fn snake_free_squares(world: &[Vec<WorldCell>]) -> Vec<Vec<bool>> {
+    let height = world.len();
+    let width = world[0].len();
+    let mut rows = vec![vec![0u8; width]; height];
+    for (y, row) in world.iter().enumerate() {
+        for x in 0..width {
+            rows[y][x] = (-RADIUS..=RADIUS)
+                .filter(|dx| row[wrap(x as i32 + dx, width)] == WorldCell::Snake)
+                .count() as u8;
+        }
+    }
+    let mut free = vec![vec![false; width]; height];
+    for (y, free_row) in free.iter_mut().enumerate() {
+        for (x, cell) in free_row.iter_mut().enumerate() {
+            *cell = (-RADIUS..=RADIUS).all(|dy| rows[wrap(y as i32 + dy, height)][x] == 0);
+        }
+    }
+    free
+}
+
+/// Tails next to the head pointing away from the direction the snake will move.
+/// The cell in front of the head must not be an obstacle, or the snake dies on its first tick.
+fn tails(world: &[Vec<WorldCell>], head: Coord) -> Vec<(Coord, Direction)> {
+    let height = world.len();
+    let width = world[0].len();
+    let cell = |dx: i32, dy: i32| world[wrap(head.y + dy, height)][wrap(head.x + dx, width)];
+    [
+        (-1, 0, Direction::RIGHT),
+        (1, 0, Direction::LEFT),
+        (0, -1, Direction::DOWN),
+        (0, 1, Direction::UP),
+    ]
+    .into_iter()
+    .filter(|(dx, dy, _)| cell(*dx, *dy) == WorldCell::Empty && cell(-dx, -dy) != WorldCell::Obstacle)
+    .map(|(dx, dy, direction)| {
+        let x = wrap(head.x + dx, width);
+        let y = wrap(head.y + dy, height);
+        (Coord::new(x as i32, y as i32), direction)
+    })
+    .collect()
+}
+
+/// With walls a square has to fit inside the field, otherwise it may wrap.
+/// A non-empty spawn zone limits where heads may be placed.
+fn heads(world: &[Vec<WorldCell>], walled: bool, zone: &[Coord]) -> Vec<Coord> {
+    let zone: HashSet<&Coord> = zone.iter().collect();
+    let height = world.len() as i32;
+    let width = world[0].len() as i32;
+    let inside = |head: Coord| {
+        (RADIUS..width - RADIUS).contains(&head.x) && (RADIUS..height - RADIUS).contains(&head.y)
+    };
+    let mut heads = Vec::new();
+    for (y, row) in snake_free_squares(world).into_iter().enumerate() {
+        for (x, free) in row.into_iter().enumerate() {
+            let head = Coord::new(x as i32, y as i32);
+            if walled && !inside(head) || !zone.is_empty() && !zone.contains(&head) {
+                continue;
+            }
+            if free && world[y][x] == WorldCell::Empty && !tails(world, head).is_empty() {
+                heads.push(head);
+            }
+        }
+    }
+    heads
+}
+
+pub fn has_space(world: &[Vec<WorldCell>], walled: bool, zone: &[Coord]) -> bool {
+    !heads(world, walled, zone).is_empty()
+}
+
+/// Picks uniformly among all valid squares, then among the empty cells around the head for the tail.
+pub fn find<R: Rng>(world: &[Vec<WorldCell>], walled: bool, zone: &[Coord], rng: &mut R) -> Result<Spawn, NoSpace> {
+    let head = *heads(world, walled, zone).choose(rng).ok_or(NoSpace)?;
+    let (tail, direction) = *tails(world, head).choose(rng).ok_or(NoSpace)?;
+    Ok(Spawn { head, tail, direction })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn no_space_on_full_board() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // a snake cell in every fifth column leaves no 5x5 square free
+        let world: Vec<Vec<WorldCell>> = (0..10)
+            .map(|_| (0..10).map(|x| if x % 5 == 0 { WorldCell::Snake } else { WorldCell::Empty }).collect())
+            .collect();
+        assert!(!has_space(&world, false, &[]));
+        assert_eq!(find(&world, false, &[], &mut rng).err(), Some(NoSpace));
+
+        let world = vec![vec![WorldCell::Snake; 10]; 10];
+        assert_eq!(find(&world, true, &[], &mut rng).err(), Some(NoSpace));
+    }
+
+    #[test]
+    fn tail_is_next_to_head() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let world = vec![vec![WorldCell::Empty; 10]; 10];
+        for _ in 0..20 {
+            let Spawn { head, tail, .. } = find(&world, true, &[], &mut rng).unwrap();
+            assert!((2..8).contains(&head.x) && (2..8).contains(&head.y));
+            assert_eq!((head.x - tail.x).abs() + (head.y - tail.y).abs(), 1);
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use std::{net::{ToSocketAddrs, SocketAddr}, time::Instant, collections::HashMap};
+use std::{net::{ToSocketAddrs, SocketAddr}, time::{Duration, Instant}, collections::HashMap, io::ErrorKind};
 
-use protobuf::Message;
+use ::protobuf::Message;
 
-use crate::{snakes::snakes::{GameConfig, game_message::{JoinMsg, self, SteerMsg, DiscoverMsg, RoleChangeMsg, AckMsg, PingMsg}, PlayerType, NodeRole, GameMessage, Direction}, tui::err::print_error};
+use crate::{snakes::{ext, validate}, snakes::snakes::{GameConfig, game_message::{JoinMsg, self, SteerMsg, DiscoverMsg, RoleChangeMsg, AckMsg, PingMsg}, PlayerType, NodeRole, GameMessage, Direction, GameState}, tui::err::print_error};
+use crate::new::base::game::{Config, Game, Mode, Role};
+use crate::new::base::protobuf;
+use crate::new::bot::{BotStrategy, View};
 
-use super::{base::Game, sockets::Sockets};
+use super::{sockets::{Sockets, MAX_DATAGRAM, player_addr}, server::Server};
 
 use anyhow::{Result, bail};
 
@@ -12,6 +15,9 @@ use crate::tui::game::GameInterface;
 
 use ncurses::*;
 
+/// How long joining waits for each answer of the master.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(3);
+
 #[derive(Debug)]
 struct PendingMsg {
     msg: Vec<u8>,
@@ -41,56 +47,94 @@ impl PendingMsg {
         }
     }
 
-    pub fn send(&mut self, sockets: &mut Sockets) -> Result<()> {
-        sockets.socket.send(&self.msg)?;
+    pub fn send(&mut self, sockets: &mut Sockets, addr: SocketAddr) -> Result<()> {
+        sockets.socket.send_to(&self.msg, addr)?;
         self.send_count += 1;
         Ok(())
     }
 }
 
+/// Who steers: a person at the terminal, or a strategy for headless robot clients
+/// that never touch ncurses.
+enum Pilot {
+    Human(GameInterface),
+    Bot(Box<dyn BotStrategy>),
+}
+
 pub struct Client {
     game: Game,
     player_name: String,
+    game_name: String,
     sockets: Sockets,
+    master_addr: SocketAddr,
     id: i32,
     role: NodeRole,
     seq: i64,
     last_mesg: Instant,
+    last_master_mesg: Instant,
     last_ping_mesg: Instant,
     pending_msgs: HashMap<i64, PendingMsg>,
     server_seq: i64,
-    interface: GameInterface,
+    pilot: Pilot,
     ping_seq: i64,
+    last_state: Option<GameState>,
+    quit: bool,
 }
 
 impl Client {
 
-    pub fn new(config: GameConfig, player_name: String, player_id: i32, role: NodeRole, addr: SocketAddr) -> Self {
-        let client = Client {
-            interface: GameInterface::new(&config),
-            game: Game::new(config),
+    pub fn new(config: GameConfig, player_name: String, game_name: String, player_id: i32, role: NodeRole, addr: SocketAddr) -> Self {
+        let config = Config::from(&config);
+        let pilot = Pilot::Human(GameInterface::new(&config));
+        let client = Self::from_parts(config, pilot, player_name, game_name, Sockets::new2(true), addr, player_id, role);
+        client.sockets.socket.connect(addr).expect("Connection to local server");
+        client
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn from_parts(config: Config, pilot: Pilot, player_name: String, game_name: String, sockets: Sockets, addr: SocketAddr, id: i32, role: NodeRole) -> Self {
+        Client {
+            pilot,
+            game: Game::new(config, 0),
             player_name,
-            sockets: Sockets::new2(true),
-            id: player_id,
+            game_name,
+            sockets,
+            master_addr: addr,
+            id,
             role,
             seq: 0,
             last_mesg: Instant::now(),
+            last_master_mesg: Instant::now(),
             last_ping_mesg: Instant::now(),
             pending_msgs: HashMap::new(),
             server_seq: -1,
             ping_seq: -1,
-        };
-        client.sockets.socket.connect(addr).expect("Connection to local server");
-        client
+            last_state: None,
+            quit: false,
+        }
     }
 
     pub fn get_local_addr(&self) -> SocketAddr {
         self.sockets.socket.local_addr().expect("Client has peer addr")
     }
 
-    fn wait_announcement(sockets: &mut Sockets, game_name: &str) -> Result<GameConfig> {
-        let mut buf = [0u8; 1024];
-        let len = sockets.socket.recv(&mut buf)?;
+    fn recv_from_master(sockets: &mut Sockets, addr: SocketAddr, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            let (len, from) = match sockets.socket.recv_from(buf) {
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    bail!("Master {} did not answer", addr);
+                }
+                received => received?,
+            };
+            if from == addr {
+                return Ok(len);
+            }
+        }
+    }
+
+    fn wait_announcement(sockets: &mut Sockets, addr: SocketAddr, game_name: &str) -> Result<GameConfig> {
+        let mut buf = [0u8; MAX_DATAGRAM];
+        let len = Self::recv_from_master(sockets, addr, &mut buf)?;
         let gm = GameMessage::parse_from_bytes(&buf[..len])?;
         if let Some(ref r#type) = gm.Type {
             match r#type {
@@ -111,18 +155,40 @@ impl Client {
         }
     }
 
-    pub fn join<T>(addr: T, game_name: &str, player_name: &str, role: NodeRole) -> Result<Self>
+    pub fn join<T>(addr: T, game_name: &str, player_name: &str, role: NodeRole, team: Option<u32>) -> Result<Self>
     where T: ToSocketAddrs {
+        let (config, sockets, addr, pid) = Self::connect(addr, game_name, player_name, role, team, PlayerType::HUMAN)?;
+        let pilot = Pilot::Human(GameInterface::new(&config));
+        Ok(Self::from_parts(config, pilot, player_name.to_owned(), game_name.to_owned(), sockets, addr, pid, role))
+    }
+
+    /// Joins as a ROBOT player steered by `strategy`, without a terminal.
+    pub fn join_bot<T>(addr: T, game_name: &str, player_name: &str, strategy: Box<dyn BotStrategy>) -> Result<Self>
+    where T: ToSocketAddrs {
+        let role = NodeRole::NORMAL;
+        let (config, sockets, addr, pid) = Self::connect(addr, game_name, player_name, role, None, PlayerType::ROBOT)?;
+        Ok(Self::from_parts(config, Pilot::Bot(strategy), player_name.to_owned(), game_name.to_owned(), sockets, addr, pid, role))
+    }
 
-        fn wait_ack(sockets: &mut Sockets) -> Result<i32> {
-            let mut buf = [0u8; 1024];
-            let len = sockets.socket.recv(&mut buf)?;
+    fn connect<T>(addr: T, game_name: &str, player_name: &str, role: NodeRole, team: Option<u32>, player_type: PlayerType) -> Result<(Config, Sockets, SocketAddr, i32)>
+    where T: ToSocketAddrs {
+
+        fn wait_ack(sockets: &mut Sockets, addr: SocketAddr) -> Result<i32> {
+            let mut buf = [0u8; MAX_DATAGRAM];
+            let len = Client::recv_from_master(sockets, addr, &mut buf)?;
             let gm = &GameMessage::parse_from_bytes(&buf[..len])?;
             if let Some(ref r#type) = gm.Type {
                 match r#type {
                     game_message::Type::Ack(_ack) => {
                         return Ok(gm.receiver_id())
                     }
+                    game_message::Type::Error(error) => {
+                        let mut ack = GameMessage::new();
+                        ack.set_ack(AckMsg::new());
+                        ack.set_msg_seq(gm.msg_seq());
+                        sockets.socket.send_to(&ack.write_to_bytes()?, addr)?;
+                        bail!("{}", error.error_message());
+                    }
                     _ => {
                         bail!("Unexpected message from server");
                     }
@@ -133,45 +199,66 @@ impl Client {
         }
 
         let mut sockets = Sockets::new3(false);
-        sockets.socket.connect(&addr)?;
+        // the server answers a join for an unknown game once and never resends it, so a lost answer must not leave the join hanging
+        sockets.socket.set_read_timeout(Some(JOIN_TIMEOUT))?;
+        let Some(addr) = addr.to_socket_addrs()?.next() else {
+            bail!("Server address was not resolved");
+        };
         let mut msg = GameMessage::new();
         msg.set_discover(DiscoverMsg::new());
         msg.set_msg_seq(0);
-        sockets.socket.send(&msg.write_to_bytes()?)?;
-        let config = Self::wait_announcement(&mut sockets, game_name)?;
+        sockets.socket.send_to(&msg.write_to_bytes()?, addr)?;
+        let config = Self::wait_announcement(&mut sockets, addr, game_name)?;
+        validate::check(&config)?;
 
         let mut join_msg = JoinMsg::new();
-        join_msg.set_player_type(PlayerType::HUMAN);
+        join_msg.set_player_type(player_type);
         join_msg.set_requested_role(role);
         join_msg.set_game_name(game_name.to_string());
         join_msg.set_player_name(player_name.to_string());
+        ext::set_requested_team(&mut join_msg, team);
         let mut msg = GameMessage::new();
         msg.set_join(join_msg);
         msg.set_msg_seq(0);
         let bytes = msg.write_to_bytes()?;
-        sockets.socket.send(&bytes)?;
+        sockets.socket.send_to(&bytes, addr)?;
 
-        let pid = wait_ack(&mut sockets)?;
+        let pid = wait_ack(&mut sockets, addr)?;
         sockets.socket.set_nonblocking(true)?;
-        Ok(Client {
-            interface: GameInterface::new(&config),
-            game: Game::new(config),
-            player_name: player_name.to_owned(),
-            sockets,
-            id: pid,
-            role,
-            seq: 0,
-            last_mesg: Instant::now(),
-            last_ping_mesg: Instant::now(),
-            pending_msgs: HashMap::new(),
-            server_seq: -1,
-            ping_seq: -1,
-        })
-        
+        Ok((Config::from(&config), sockets, addr, pid))
     }
 
     fn print(&self) {
-        self.interface.print(&self.game, self.id);
+        if let Pilot::Human(interface) = &self.pilot {
+            interface.print(&self.game, self.id);
+        }
+    }
+
+    fn report(&self, msg: &str) {
+        match &self.pilot {
+            Pilot::Human(interface) => interface.print_error(msg),
+            Pilot::Bot(_) => eprintln!("{}: {}", self.player_name, msg),
+        }
+    }
+
+    /// Asks the strategy for a direction after every new state; in endless games a dead robot
+    /// asks to play again, in rounds it waits for the master to respawn it.
+    fn steer_bot(&mut self) {
+        let Pilot::Bot(strategy) = &mut self.pilot else {
+            return;
+        };
+        let Some(snake) = self.game.snakes.get(&self.id) else {
+            let rejoining = self.pending_msgs.values().any(|pending_msg| pending_msg.tpe == "RoleChange");
+            if self.game.config.mode == Mode::Endless && !rejoining {
+                self.send_change_player();
+            }
+            return;
+        };
+        let current = snake.head_direction;
+        let direction = strategy.steer(&View::new(&self.game, self.id));
+        if direction != current {
+            self.send_steer(direction.into());
+        }
     }
 
     fn send_steer(&mut self, dir: Direction) {
@@ -208,21 +295,32 @@ impl Client {
     }
 
     pub fn prepare(&mut self) {
-        self.sockets.socket.set_nonblocking(true).expect("set client socket nonblocking");
-        timeout(50);
+        match self.pilot {
+            Pilot::Human(_) => {
+                self.sockets.socket.set_nonblocking(true).expect("set client socket nonblocking");
+                timeout(50);
+            }
+            Pilot::Bot(_) => {
+                // without getch to wait on, block on the socket for a moment instead of spinning
+                let wait = (self.game.config.state_delay / 10).max(Duration::from_millis(1));
+                self.sockets.socket.set_nonblocking(false).expect("set client socket blocking");
+                self.sockets.socket.set_read_timeout(Some(wait)).expect("set client socket read timeout");
+            }
+        }
     }
 
     fn check_pending(&mut self) {
-        let delay = self.game.config.state_delay_ms() as u128 / 10;
+        let delay = self.game.config.state_delay.as_millis() / 10;
         let now = Instant::now();
+        let addr = self.master_addr;
         self.pending_msgs.retain(|_, v| {
             match v.send_time {
                 None => {
-                    v.send(&mut self.sockets).expect("Correct send")
+                    v.send(&mut self.sockets, addr).expect("Correct send")
                 }
                 Some(t) => {
                     if (now - t).as_millis() > delay {
-                        v.send(&mut self.sockets).expect("Correct send")
+                        v.send(&mut self.sockets, addr).expect("Correct send")
                     }
                 }
             }
@@ -240,7 +338,7 @@ impl Client {
         let mut gm = GameMessage::new();
         gm.set_ack(AckMsg::new());
         gm.set_msg_seq(seq);
-        self.sockets.socket.send(&gm.write_to_bytes().expect("written ack bytes")).expect("ack send");
+        self.sockets.socket.send_to(&gm.write_to_bytes().expect("written ack bytes"), self.master_addr).expect("ack send");
         self.last_mesg = Instant::now();
     }
 
@@ -258,7 +356,7 @@ impl Client {
 
     fn check_ping(&mut self) {
         let now = Instant::now();
-        let delay = self.game.config.state_delay_ms() as u128 / 10;
+        let delay = self.game.config.state_delay.as_millis() / 10;
         if (now - self.last_mesg).as_millis() > delay {
             if (now - self.last_ping_mesg).as_millis() > delay {
                 self.send_ping();
@@ -266,42 +364,66 @@ impl Client {
         }
     }
 
-    pub fn action(&mut self) -> bool {
-        let mut buf = [0u8; 1024];
-        if let Ok(len) = self.sockets.socket.recv(&mut buf) {
-            if len == 0 {
-                return false;
+    fn is_master_lost(&self) -> bool {
+        let timeout = self.game.config.state_delay.as_secs_f32() * 0.8f32;
+        self.last_master_mesg.elapsed().as_secs_f32() > timeout
+    }
+
+    fn switch_master(&mut self, addr: SocketAddr) {
+        self.master_addr = addr;
+        self.server_seq = -1;
+        self.last_master_mesg = Instant::now();
+    }
+
+    fn switch_to_deputy(&mut self) -> bool {
+        // addresses are only known from the wire, the engine doesn't keep them
+        let deputy = self.last_state.as_ref()
+            .and_then(|state| state.players.players.iter().find(|player| player.role() == NodeRole::DEPUTY))
+            .and_then(|player| Some((player.id(), player_addr(player)?)));
+        if let Some((deputy_id, addr)) = deputy {
+            if addr != self.master_addr {
+                self.game.players.retain(|_, player| player.role != Role::Master);
+                if let Some(player) = self.game.players.get_mut(&deputy_id) {
+                    player.role = Role::Master;
+                }
+                self.switch_master(addr);
+                return true;
             }
+        }
+        false
+    }
+
+    pub fn quitting(&self) -> bool {
+        self.quit
+    }
+
+    fn into_server(self) -> Result<Server> {
+        let Client { game, game_name, sockets, id, pilot, last_state, .. } = self;
+        drop(pilot);
+        let mut server = Server::from_state(last_state.unwrap_or_default(), GameConfig::from(&game.config), game_name)?;
+        server.take_over(id, sockets);
+        Ok(server)
+    }
+
+    pub fn action(&mut self) -> bool {
+        let mut buf = [0u8; MAX_DATAGRAM];
+        if let Ok((len, addr)) = self.sockets.socket.recv_from(&mut buf) {
             if let Ok(gm) = GameMessage::parse_from_bytes(&buf[..len]) {
-                let seq = gm.msg_seq();
-                let _sender_id = gm.sender_id();
-                let _receiver_id = gm.receiver_id();
-                if let Some(tpe) = gm.Type {
-                    match tpe {
-                        game_message::Type::State(state) => {
-                            self.send_ack(seq);
-                            if seq >= self.server_seq {
-                                self.game.apply_state(state.state.unwrap(), seq);
-                                self.print();
-                                self.server_seq = seq;
-                            }
-                        }
-                        game_message::Type::Ack(_) => {
-                            self.process_ack(seq);
-                            self.last_mesg = Instant::now();
-                        }
-                        game_message::Type::RoleChange(chnge) => {
-                            self.send_ack(seq);
-                            if seq >= self.server_seq {
-                                self.role = chnge.receiver_role();
-                                self.server_seq = seq;
-                            }
-                        }
-                        _ => {}
+                if let Some(game_message::Type::RoleChange(ref chnge)) = gm.Type {
+                    if addr != self.master_addr && chnge.sender_role() == NodeRole::MASTER {
+                        self.switch_master(addr);
                     }
                 }
+                if addr == self.master_addr {
+                    self.last_master_mesg = Instant::now();
+                    self.process_message(gm);
+                }
             }
-            match getch() {
+            let key = match self.pilot {
+                Pilot::Human(_) => getch(),
+                Pilot::Bot(_) => ERR,
+            };
+            match key {
                 KEY_LEFT => {
                     self.send_steer(Direction::LEFT);
                 }
@@ -320,6 +442,9 @@ impl Client {
                 KEY_BACKSPACE => {
                     self.send_change_viewer();
                 }
+                113 => { // q
+                    self.quit = true;
+                }
                 ERR => {
 
                 }
@@ -333,32 +458,83 @@ impl Client {
         //     self.interface.dbg(&format!("{seq} {} {}\n", msg.send_count, msg.tpe));
         // }
         // self.interface.dbg(&format!("{}\n", self.pending_msgs.len()));
-        true
+        !self.quit && !self.is_master_lost() && self.role != NodeRole::MASTER
     }
 
-    pub fn play(&mut self) {
+    fn process_message(&mut self, gm: GameMessage) {
+        let seq = gm.msg_seq();
+        let _sender_id = gm.sender_id();
+        let _receiver_id = gm.receiver_id();
+        if let Some(tpe) = gm.Type {
+            match tpe {
+                game_message::Type::State(state) => {
+                    self.send_ack(seq);
+                    if seq >= self.server_seq {
+                        let state = state.state.unwrap();
+                        let round = ext::round(&state).map_or(self.game.round, |(round, _)| round);
+                        if self.last_state.is_some() && round != self.game.round {
+                            if let Pilot::Human(interface) = &self.pilot {
+                                interface.print_summary(&self.game.summary());
+                            }
+                        }
+                        if let Err(e) = protobuf::apply_state(&mut self.game, &state) {
+                            self.report(&e.to_string());
+                            return;
+                        }
+                        self.last_state = Some(state);
+                        self.print();
+                        self.steer_bot();
+                        self.server_seq = seq;
+                    }
+                }
+                game_message::Type::Ack(_) => {
+                    self.process_ack(seq);
+                    self.last_mesg = Instant::now();
+                }
+                game_message::Type::RoleChange(chnge) => {
+                    self.send_ack(seq);
+                    if seq >= self.server_seq && chnge.has_receiver_role() {
+                        self.role = chnge.receiver_role();
+                        self.server_seq = seq;
+                    }
+                }
+                game_message::Type::Error(error) => {
+                    self.send_ack(seq);
+                    self.report(error.error_message());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn play(mut self) {
         self.prepare();
         loop {
             if !self.action() {
+                if self.quit {
+                    break;
+                }
                 match self.role {
-                    NodeRole::MASTER => break,
-                    NodeRole::DEPUTY => {
+                    NodeRole::MASTER | NodeRole::DEPUTY if matches!(self.pilot, Pilot::Bot(_)) => {
+                        self.report("robots can't host a game");
                         break;
                     }
+                    NodeRole::MASTER | NodeRole::DEPUTY => {
+                        let player_name = self.player_name.clone();
+                        match self.into_server() {
+                            Ok(mut server) => server.run(&player_name),
+                            Err(e) => print_error(e.to_string()),
+                        }
+                        return;
+                    }
                     NodeRole::NORMAL | NodeRole::VIEWER => {
-                        // for player in 
-                        for player in self.game.players.values() {
-                            if player.role.unwrap().unwrap() == NodeRole::DEPUTY {
-                                if let Err(e) = self.sockets.socket.connect(player.ip_address()) {
-                                    print_error(e);
-                                    break;
-                                } else {
-                                    continue;
-                                }
+                        if !self.switch_to_deputy() {
+                            match self.pilot {
+                                Pilot::Human(_) => print_error("There is not Deputy players"),
+                                Pilot::Bot(_) => self.report("There is not Deputy players"),
                             }
+                            break;
                         }
-                        print_error("There is not Deputy players");
-                        break;
                     }
                 }
             }
@@ -366,4 +542,4 @@ impl Client {
     }
 
 
-}
+}
\ No newline at end of file
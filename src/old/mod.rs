@@ -1,4 +1,3 @@
-pub mod base;
 pub mod server;
 pub mod sockets;
 pub mod client;
@@ -1,17 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::{Ipv4Addr, SocketAddr};
 use std::time::Instant;
-use protobuf::{Message, MessageField};
+use ::protobuf::{Message, MessageField};
 use super::client::Client;
-use super::sockets::Sockets;
-use crate::snakes::snakes::game_state::Snake;
-use crate::snakes::snakes::game_state::snake::SnakeState;
-use crate::snakes::snakes::{GameAnnouncement, GameConfig, GameMessage, GamePlayer, GamePlayers, game_message, GameState, NodeRole, Direction};
-use crate::snakes::snakes::game_message::{AnnouncementMsg, AckMsg, StateMsg, ErrorMsg, RoleChangeMsg};
-use anyhow::Result;
-
-use super::base::{Game, WorldCell};
+use super::sockets::{Sockets, MAX_DATAGRAM, player_addr, set_player_addr};
+use crate::new::base::game::{Config, Direction, Game, Mode, Player, PlayerKind, Role, SnakeState};
+use crate::new::bot::{self, BotStrategy, View};
+use crate::new::base::protobuf;
+use crate::snakes::{ext, validate};
+use crate::snakes::snakes::{GameAnnouncement, GameConfig, GameMessage, GamePlayer, GamePlayers, game_message, GameState, NodeRole, PlayerType};
+use crate::snakes::snakes::game_message::{AnnouncementMsg, AckMsg, StateMsg, ErrorMsg, RoleChangeMsg, JoinMsg};
+use anyhow::{Result, bail};
 
 struct PendingMsg {
     msg: Vec<u8>,
@@ -30,7 +30,7 @@ impl PendingMsg {
         }
     }
 
-    pub fn send(&mut self, sockets: &mut Sockets) -> Result<(), io::Error> {
+    pub fn send(&mut self, sockets: &Sockets) -> Result<(), io::Error> {
         sockets
             .socket
             .send_to(&self.msg, self.addr)
@@ -40,70 +40,163 @@ impl PendingMsg {
             })
     }
 }
-pub struct Server {
+
+struct Room {
     game: Game,
-    sockets: Sockets,
     name: String,
     seq: i64,
     id: i32,
-    multicast_instant: Instant,
     game_instant: Instant,
     pending_msgs: HashMap<i64, PendingMsg>,
     player_timeout: HashMap<SocketAddr, Instant>,
     player_id: i32,
     addrs: HashMap<SocketAddr, i32>,
+    steer_seqs: HashMap<i32, i64>,
+    steers: HashMap<i32, Direction>,
+    /// Robot players steered by this node, they have no address.
+    bots: BTreeMap<i32, Box<dyn BotStrategy>>,
 }
 
-impl Server {
-    pub fn new(config: GameConfig, name: String) -> Self {
-        Server {
-            game: Game::new(config),
-            sockets: Sockets::new(true),
-            name: name,
+pub struct Server {
+    rooms: Vec<Room>,
+    sockets: Sockets,
+    seq: i64,
+    multicast_instant: Instant,
+}
+
+impl Room {
+    fn new(config: GameConfig, name: String) -> Self {
+        Room {
+            game: Game::new(Config::from(&config), rand::random()),
+            name,
             seq: 0,
             id: 0,
-            multicast_instant: Instant::now(),
             game_instant: Instant::now(),
             pending_msgs: HashMap::new(),
             player_timeout: HashMap::new(),
             addrs: HashMap::new(),
             player_id: 1,
+            steer_seqs: HashMap::new(),
+            steers: HashMap::new(),
+            bots: BTreeMap::new(),
         }
     }
 
-    fn get_announcement(&mut self) -> GameMessage {
+    fn from_state(state: GameState, config: GameConfig, name: String) -> Result<Self> {
+        let mut game = Game::new(Config::from(&config), 0);
+        protobuf::apply_state(&mut game, &state)?;
+        // the previous master's random sequence can't be resumed, the game goes on with a new seed
+        game.reseed(rand::random());
+        let mut addrs = HashMap::new();
+        let mut player_timeout = HashMap::new();
+        for player in state.players.players.iter() {
+            if let Some(addr) = player_addr(player) {
+                addrs.insert(addr, player.id());
+                player_timeout.insert(addr, Instant::now());
+            }
+        }
+        let id = game.players.values()
+            .find(|player| player.role == Role::Master)
+            .map_or(0, |player| player.id);
+        let player_id = game.players.keys().chain(game.snakes.keys()).max().map_or(1, |max_id| max_id + 1);
+        // robots of the previous master are adopted by the new one
+        let bots = state.players.players.iter()
+            .filter(|player| player.type_() == PlayerType::ROBOT && player_addr(player).is_none())
+            .filter_map(|player| {
+                let seed = rand::random();
+                let strategy = ext::strategy(player)
+                    .and_then(|name| bot::by_name(name, seed))
+                    .or_else(|| bot::by_name(bot::DEFAULT_STRATEGY, seed))?;
+                Some((player.id(), strategy))
+            })
+            .collect();
+        Ok(Room {
+            game,
+            name,
+            seq: 0,
+            id,
+            game_instant: Instant::now(),
+            pending_msgs: HashMap::new(),
+            player_timeout,
+            player_id,
+            addrs,
+            steer_seqs: HashMap::new(),
+            steers: HashMap::new(),
+            bots,
+        })
+    }
+
+    fn take_over(&mut self, id: i32) {
+        let old_masters: Vec<i32> = self.game.players.values()
+            .filter(|player| player.role == Role::Master && player.id != id)
+            .map(|player| player.id)
+            .collect();
+        for master_id in old_masters {
+            self.game.players.remove(&master_id);
+            if let Some(snak) = self.game.snakes.get_mut(&master_id) {
+                snak.state = SnakeState::Zombie;
+            }
+        }
+        self.addrs.retain(|_, pid| *pid != id && self.game.players.contains_key(pid));
+        self.player_timeout.retain(|addr, _| self.addrs.contains_key(addr));
+        if let Some(player) = self.game.players.get_mut(&id) {
+            player.role = Role::Master;
+        }
+        self.id = id;
+        let receivers: Vec<(SocketAddr, i32)> = self.addrs.iter().map(|(addr, pid)| (*addr, *pid)).collect();
+        for (addr, pid) in receivers {
+            let role = self.game.players.get(&pid).map_or(Role::Viewer, |player| player.role);
+            let gm = self.get_chnge_role(role.into(), pid);
+            self.send(&gm, addr);
+        }
+    }
+
+    fn owns(&self, addr: &SocketAddr) -> bool {
+        self.addrs.contains_key(addr) || self.pending_msgs.values().any(|pending_msg| pending_msg.addr == *addr)
+    }
+
+    fn addr_of(&self, id: i32) -> Option<SocketAddr> {
+        self.addrs.iter().find(|(_, pid)| **pid == id).map(|(addr, _)| *addr)
+    }
+
+    fn get_players(&self) -> GamePlayers {
+        let mut players = GamePlayers::new();
+        players.players = self.game.players.values().map(|player| {
+            let mut game_player = GamePlayer::from(player);
+            ext::set_strategy(&mut game_player, self.bots.get(&player.id).map(|strategy| strategy.name()));
+            // the sender's own entry is left without an address
+            if let Some(addr) = self.addr_of(player.id).filter(|_| player.id != self.id) {
+                set_player_addr(&mut game_player, addr);
+            }
+            game_player
+        }).collect();
+        players
+    }
+
+    fn get_announcement(&self) -> GameAnnouncement {
         let mut current_game_info = GameAnnouncement::new();
         current_game_info.game_name = Some(self.name.clone());
-        let mut players = GamePlayers::new();
-        players.players = self.game.players.values().cloned().collect();
-        current_game_info.players = MessageField::some(players);
-        current_game_info.config = MessageField::some(self.game.config.clone());
-        current_game_info.set_can_join(true);
-        let mut announcement = AnnouncementMsg::new();
-        announcement.games.push(current_game_info);
-        let mut game_message = GameMessage::new();
-        game_message.set_msg_seq(self.seq);
-        self.seq += 1;
-        // game_message.set_receiver_id(0);
-        game_message.set_sender_id(self.id);
-        game_message.set_announcement(announcement);
-        game_message
+        current_game_info.players = MessageField::some(self.get_players());
+        current_game_info.config = MessageField::some(GameConfig::from(&self.game.config));
+        current_game_info.set_can_join(self.can_join());
+        current_game_info
     }
 
-    fn do_multicast(&mut self) {
-        let now = Instant::now();
-        if (now - self.multicast_instant).as_millis() > 1000 {
-            let announcement = self.get_announcement();
-            self.sockets.socket.send_to(&announcement.write_to_bytes().expect("announcement bytes"), "239.192.0.4:9192").expect("multicast send");
-            self.multicast_instant = now;
-        }
+    fn is_full(&self) -> bool {
+        self.game.config.max_players.is_some_and(|max_players| {
+            self.game.snakes.values().filter(|snak| snak.state == SnakeState::Alive).count() >= max_players
+        })
     }
 
-    fn get_chnge_role(&mut self, _role: NodeRole, id: i32) -> GameMessage {
+    fn can_join(&self) -> bool {
+        !self.is_full() && self.game.has_free_coord5x5()
+    }
+
+    fn get_chnge_role(&mut self, role: NodeRole, id: i32) -> GameMessage {
         let mut gm = GameMessage::new();
         let mut rc = RoleChangeMsg::new();
         rc.set_sender_role(NodeRole::MASTER);
-        rc.set_receiver_role(NodeRole::VIEWER);
+        rc.set_receiver_role(role);
         gm.set_role_change(rc);
         gm.set_msg_seq(self.seq);
         gm.set_sender_id(self.id);
@@ -114,20 +207,19 @@ impl Server {
 
     fn do_state(&mut self) {
         let now = Instant::now();
-        if (now - self.game_instant).as_millis() > self.game.config.state_delay_ms() as u128 {
-            let dead_ids = self.game.update();
-            for dead_id in dead_ids {
-                if let Some(player) = self.game.players.get(&dead_id) {
-                    let mut gm = GameMessage::new();
-                    let mut rc = RoleChangeMsg::new();
-                    rc.set_sender_role(NodeRole::MASTER);
-                    rc.set_receiver_role(NodeRole::VIEWER);
-                    gm.set_role_change(rc);
-                    gm.set_msg_seq(self.seq);
-                    gm.set_sender_id(self.id);
-                    gm.set_receiver_id(player.id());
-                    self.seq += 1;
-                    self.pending_msgs.insert(gm.msg_seq(), PendingMsg::new(&gm, player.ip_address().to_socket_addrs().unwrap().next().unwrap()));
+        if now - self.game_instant > self.game.config.state_delay {
+            // the state that ended the round has already been sent, so clients saw the final scores
+            if self.game.round_over() {
+                self.new_round();
+            } else {
+                if self.game.config.mode == Mode::Endless {
+                    self.spawn_bots();
+                }
+                self.steer_bots();
+                self.apply_steers();
+                let tick = self.game.tick();
+                for dead_id in tick.dead {
+                    self.set_role(dead_id, Role::Viewer);
                 }
             }
             self.get_state();
@@ -135,15 +227,81 @@ impl Server {
         }
     }
 
+    /// Switches a player between playing and watching; master and deputy keep their node roles.
+    fn set_role(&mut self, id: i32, role: Role) {
+        if id == self.id {
+            return;
+        }
+        if let Some(player) = self.game.players.get_mut(&id) {
+            if player.role == role || player.role == Role::Deputy {
+                return;
+            }
+            player.role = role;
+            if let Some(addr) = self.addr_of(id) {
+                let gm = self.get_chnge_role(role.into(), id);
+                self.send(&gm, addr);
+            }
+        }
+    }
+
+    fn new_round(&mut self) {
+        self.steers.clear();
+        for id in self.game.restart() {
+            if self.game.players.get(&id).is_some_and(|player| player.role == Role::Viewer) {
+                self.set_role(id, Role::Normal);
+            }
+        }
+        self.spawn_bots();
+    }
+
+    fn add_bot(&mut self, name: String, strategy: Box<dyn BotStrategy>) {
+        let mut player = Player::new(self.player_id, name, Role::Viewer, PlayerKind::Robot);
+        player.team = self.game.pick_team(None);
+        self.player_id += 1;
+        self.bots.insert(player.id, strategy);
+        self.game.players.insert(player.id, player);
+        self.spawn_bots();
+    }
+
+    /// Robots without a snake rejoin like a player pressing enter would.
+    fn spawn_bots(&mut self) {
+        let waiting: Vec<i32> = self.bots.keys().filter(|id| !self.game.snakes.contains_key(id)).copied().collect();
+        for id in waiting {
+            if self.is_full() || self.game.spawn_snake(id).is_err() {
+                break;
+            }
+            if let Some(player) = self.game.players.get_mut(&id) {
+                player.role = Role::Normal;
+            }
+        }
+    }
+
+    fn steer_bots(&mut self) {
+        for (id, strategy) in self.bots.iter_mut().filter(|(id, _)| self.game.snakes.contains_key(id)) {
+            let direction = strategy.steer(&View::new(&self.game, *id));
+            if self.game.can_steer(*id, direction) {
+                self.steers.insert(*id, direction);
+            }
+        }
+    }
+
+    fn apply_steers(&mut self) {
+        for (id, direction) in self.steers.drain() {
+            if let Some(p) = self.game.snakes.get_mut(&id) {
+                p.head_direction = direction;
+            }
+        }
+    }
+
     fn check_timeout(&mut self) {
         let now = Instant::now();
-        let delay = self.game.config.state_delay_ms() as f32 / 1000f32 * 0.8f32;
+        let delay = self.game.config.state_delay.as_secs_f32() * 0.8f32;
         self.player_timeout.retain(|addr, timeout| {
             if (now - *timeout).as_secs_f32() > delay {
                 if let Some(id) = self.addrs.get(addr) {
                     self.game.players.remove(id);
                     if let Some(snak) = self.game.snakes.get_mut(id) {
-                        snak.set_state(SnakeState::ZOMBIE);
+                        snak.state = SnakeState::Zombie;
                     }
                 }
                 self.addrs.remove(addr);
@@ -157,6 +315,17 @@ impl Server {
         self.pending_msgs.insert(msg.msg_seq(), PendingMsg::new(&msg, addr));
     }
 
+    fn send_error(&mut self, message: &str, addr: SocketAddr) {
+        let mut error = ErrorMsg::new();
+        error.set_error_message(message.to_string());
+        let mut game_msg = GameMessage::new();
+        game_msg.set_msg_seq(self.seq);
+        game_msg.set_sender_id(self.id);
+        game_msg.set_error(error);
+        self.seq += 1;
+        self.send(&game_msg, addr);
+    }
+
     fn ack_pending(&mut self, seq: i64) {
         let _res = self.pending_msgs.remove(&seq);
     }
@@ -165,48 +334,41 @@ impl Server {
         self.player_timeout.insert(addr, Instant::now());
     }
 
-    fn check_pending(&mut self) {
+    fn check_pending(&mut self, sockets: &Sockets) {
         let now = Instant::now();
-        let delay = self.game.config.state_delay_ms() as u128 / 10;
+        let delay = self.game.config.state_delay.as_millis() / 10;
         self.pending_msgs.retain(|_, pending_msg| {
             if let Some(send_time) = pending_msg.send_time {
                 if (now - send_time).as_millis() > delay {
-                    pending_msg.send(&mut self.sockets).expect("send pending");
+                    pending_msg.send(sockets).expect("send pending");
                     pending_msg.send_time = Some(now);
                 }
             } else {
-                pending_msg.send(&mut self.sockets).expect("send pending");
+                pending_msg.send(sockets).expect("send pending");
                 pending_msg.send_time = Some(now);
             }
             return pending_msg.send_count < 5;
         })
     }
-    
-    pub fn get_state(&mut self) {
-        let mut state = GameState::new();
-        state.foods.clone_from(&self.game.food);
-        state.snakes = self.game.snakes.values().cloned().collect();
-        let mut players = GamePlayers::new();
-        players.players = self.game.players.values().cloned().collect();
-        state.players = Some(players).into();
-        state.set_state_order(self.game.order);
+
+    fn get_state(&mut self) {
+        let mut state = protobuf::state(&self.game);
+        state.players = Some(self.get_players()).into();
         let mut state_msg = StateMsg::new();
         state_msg.state = Some(state).into();
         let mut msg = GameMessage::new();
         msg.set_state(state_msg);
         msg.set_sender_id(self.id);
         let pm = &mut self.pending_msgs;
-        for player in self.game.players.values() {
+        for (addr, id) in self.addrs.iter() {
             msg.set_msg_seq(self.seq);
-            msg.set_receiver_id(player.id());
-            pm.insert(msg.msg_seq(), PendingMsg::new(&msg, player.ip_address().to_socket_addrs().expect("player ip to socket").next().expect("one ip")));
-            // Self::send(pm, &msg, player.ip_address().to_socket_addrs().expect("player ip to socket").next().expect("one ip"));
-            // self.send(&msg, player.ip_address().to_socket_addrs().expect("player ip to socket").next().expect("one ip"));
+            msg.set_receiver_id(*id);
+            pm.insert(msg.msg_seq(), PendingMsg::new(&msg, *addr));
             self.seq += 1;
         }
     }
-    
-    fn send_ack(&self, seq: i64, receiver_id: Option<i32>, addr: &SocketAddr) {
+
+    fn send_ack(&self, sockets: &Sockets, seq: i64, receiver_id: Option<i32>, addr: &SocketAddr) {
         let mut game_msg = GameMessage::new();
         game_msg.set_ack(AckMsg::new());
         game_msg.set_sender_id(self.id);
@@ -214,187 +376,314 @@ impl Server {
         if let Some(pid) = receiver_id {
             game_msg.set_receiver_id(pid);
         }
-        self.sockets.socket.send_to(&game_msg.write_to_bytes().expect("written ack"), addr).expect("send ack");
+        sockets.socket.send_to(&game_msg.write_to_bytes().expect("written ack"), addr).expect("send ack");
     }
 
-    fn receive_message(&mut self) {
-        let mut buf = [0u8; 1024];
-        if let Ok((len, addr)) = self.sockets.socket.recv_from(&mut buf) {
-            if let Ok(msg) = GameMessage::parse_from_bytes(&buf[..len]) {
-                if let Some(t) = &msg.Type {
-                    match t {
-                        game_message::Type::Ping(_ping_msg) => {
-                            // self.get_player_by_ip(&addr).and_then(|player| player.set_ping(ping_msg.ping()));
-                            self.send_ack(msg.msg_seq(), Some(msg.sender_id()), &addr);
-                            self.update_timeout(addr);
-                        }
-                        game_message::Type::Ack(_ack) => {
-                            // self.send_ack(msg.msg_seq(), Some(msg.sender_id()), &addr)
-                            self.ack_pending(msg.msg_seq());
-                            self.update_timeout(addr);
-                        }
-                        game_message::Type::Announcement(_announcement) => {
+    fn join(&mut self, sockets: &Sockets, msg: &GameMessage, join: &JoinMsg, addr: SocketAddr) {
+        if join.requested_role() != NodeRole::VIEWER {
+            if self.is_full() {
+                self.send_error("game is full", addr);
+                return;
+            }
+            // late players wait for the next round
+            if self.game.mid_round() {
+                self.game.enter(self.player_id);
+            } else if let Err(no_space) = self.game.spawn_snake(self.player_id) {
+                self.send_error(&no_space.to_string(), addr);
+                return;
+            }
+        }
+        self.send_ack(sockets, msg.msg_seq(), Some(self.player_id), &addr);
+        let mut player = Player::new(
+            self.player_id,
+            join.player_name().to_string(),
+            join.requested_role().into(), // TODO: change
+            join.player_type().into(),
+        );
+        player.team = self.game.pick_team(ext::requested_team(join));
+        self.player_id += 1;
+        self.addrs.insert(addr, player.id);
+        self.game.players.insert(player.id, player);
+        self.update_timeout(addr);
+    }
 
-                        }
-                        game_message::Type::Discover(_discover) => {
-                            let announcement = self.get_announcement();
-                            if let Ok(bytes) = announcement.write_to_bytes() {
-                                let _ = self.sockets.socket.send_to(&bytes, &addr);
-                            }
-                        }
-                        game_message::Type::Error(_error) => {
-                            self.send_ack(msg.msg_seq(), Some(msg.sender_id()), &addr);
-                            self.update_timeout(addr);
-                        }
-                        game_message::Type::Join(join) => {
-                            self.send_ack(msg.msg_seq(), Some(self.player_id), &addr);
-                            let mut player = GamePlayer::new();
-                            player.set_name(join.player_name().to_string());
-                            player.set_ip_address(addr.to_string());
-                            player.set_role(join.requested_role()); // TODO: change
-                            player.set_type(join.player_type());
-                            player.set_id(self.player_id);
-                            self.player_id += 1;
-                            player.set_score(0);
-                            if join.requested_role() != NodeRole::VIEWER {
-                                if let Some((head, tail, dir)) = self.game.get_free_coord5x5() {
-                                    self.game.world[head.y() as usize][head.x() as usize] = WorldCell::Snake;
-                                    self.game.world[tail.y() as usize][tail.x() as usize] = WorldCell::Snake;
-                                    let mut snake = Snake::new();
-                                    snake.points.push(tail);
-                                    snake.points.push(head);
-                                    snake.set_head_direction(dir);
-                                    snake.set_state(SnakeState::ALIVE);
-                                    snake.set_player_id(player.id());
-                                    self.game.snakes.insert(player.id(), snake);
-                                    self.addrs.insert(addr, player.id());
-                                    self.game.players.insert(player.id(), player);
+    fn receive_message(&mut self, sockets: &Sockets, msg: GameMessage, addr: SocketAddr) {
+        if let Some(t) = &msg.Type {
+            match t {
+                game_message::Type::Ping(_ping_msg) => {
+                    // self.get_player_by_ip(&addr).and_then(|player| player.set_ping(ping_msg.ping()));
+                    self.send_ack(sockets, msg.msg_seq(), Some(msg.sender_id()), &addr);
+                    self.update_timeout(addr);
+                }
+                game_message::Type::Ack(_ack) => {
+                    // self.send_ack(msg.msg_seq(), Some(msg.sender_id()), &addr)
+                    self.ack_pending(msg.msg_seq());
+                    self.update_timeout(addr);
+                }
+                game_message::Type::Announcement(_announcement) => {
+
+                }
+                game_message::Type::Discover(_discover) => {
+
+                }
+                game_message::Type::Error(_error) => {
+                    self.send_ack(sockets, msg.msg_seq(), Some(msg.sender_id()), &addr);
+                    self.update_timeout(addr);
+                }
+                game_message::Type::Join(join) => {
+                    self.join(sockets, &msg, join, addr);
+                }
+                game_message::Type::RoleChange(role_change) => {
+                    self.send_ack(sockets, msg.msg_seq(), Some(msg.sender_id()), &addr);
+                    let id = self.addrs.get(&addr).copied();
+                    if let Some((id, role)) = id.and_then(|id| self.game.players.get(&id).map(|player| (id, player.role))) {
+                        let new_role = Role::from(role_change.sender_role());
+                        // master and deputy keep their node roles while switching between playing and watching
+                        let keeps_role = matches!(role, Role::Master | Role::Deputy);
+                        let mut changed = false;
+                        if new_role != role {
+                            if new_role == Role::Viewer {
+                                self.game.snakes.entry(id).and_modify(|snak| snak.state = SnakeState::Zombie);
+                                changed = true;
+                            } else if new_role == Role::Normal {
+                                if let Some(snak) = self.game.snakes.get_mut(&id) {
+                                    snak.state = SnakeState::Alive;
+                                    changed = true;
+                                } else if self.is_full() {
+                                    self.send_error("game is full", addr);
+                                } else if self.game.mid_round() {
+                                    self.game.enter(id);
+                                    changed = true;
                                 } else {
-                                    let mut error = ErrorMsg::new();
-                                    error.set_error_message("no available space for snake".to_string());
-                                    let mut game_msg = GameMessage::new();
-                                    game_msg.set_msg_seq(0);
-                                    game_msg.set_error(error);
-                                    if let Ok(bytes) = game_msg.write_to_bytes() {
-                                        let _ = self.sockets.socket.send_to(&bytes, addr);
+                                    match self.game.spawn_snake(id) {
+                                        Ok(()) => changed = true,
+                                        Err(no_space) => self.send_error(&no_space.to_string(), addr),
                                     }
                                 }
-                            } else {
-                                self.addrs.insert(addr, player.id());
-                                self.game.players.insert(player.id(), player);
                             }
-                            self.update_timeout(addr);
                         }
-                        game_message::Type::RoleChange(role_change) => {
-                            self.send_ack(msg.msg_seq(), Some(msg.sender_id()), &addr);
-                            if let Some(id) = self.addrs.get(&addr) {
-                                let free_5x5 = self.game.get_free_coord5x5();
-                                if let Some(player) = self.game.players.get_mut(id) {
-                                    let new_role = role_change.sender_role();
-                                    if new_role != player.role() {
-                                        if new_role == NodeRole::VIEWER {
-                                            self.game.snakes.entry(*id).and_modify(|snak| snak.set_state(SnakeState::ZOMBIE));
-                                            player.set_role(new_role);
-                                        } else if new_role == NodeRole::NORMAL {
-                                            if let Some(snak) = self.game.snakes.get_mut(&player.id()) {
-                                                snak.set_state(SnakeState::ALIVE);
-                                                player.set_role(new_role);
-                                            } else {
-                                                if let Some((head, tail, dir)) = free_5x5 {
-                                                    self.game.world[head.y() as usize][head.x() as usize] = WorldCell::Snake;
-                                                    self.game.world[tail.y() as usize][tail.x() as usize] = WorldCell::Snake;
-                                                    let mut snake = Snake::new();
-                                                    snake.points.push(tail);
-                                                    snake.points.push(head);
-                                                    snake.set_head_direction(dir);
-                                                    snake.set_state(SnakeState::ALIVE);
-                                                    snake.set_player_id(*id);
-                                                    self.game.snakes.insert(*id, snake);
-                                                    player.set_role(new_role);
-                                                } else {
-                                                    
-                                                }
-                                            }
-                                        }
-                                    } 
-                                }
+                        if changed && !keeps_role {
+                            if let Some(player) = self.game.players.get_mut(&id) {
+                                player.role = new_role;
                             }
-                            self.update_timeout(addr);
-                        }
-                        game_message::Type::State(_state) => {
-                            self.send_ack(msg.msg_seq(), Some(msg.sender_id()), &addr);
-                            self.update_timeout(addr);
                         }
-                        game_message::Type::Steer(steer) => {
-                            self.send_ack(msg.msg_seq(), Some(msg.sender_id()), &addr);
-                            if let Some(id) = self.addrs.get(&addr) {
-                                if let Some(player) = self.game.players.get(id) {
-                                    if player.role() != NodeRole::VIEWER {
-                                        if let Some(p) = self.game.snakes.get_mut(&id) {
-                                            let opposite_dir = match p.head_direction() {
-                                                Direction::UP => Direction::DOWN,
-                                                Direction::DOWN => Direction::UP,
-                                                Direction::LEFT => Direction::RIGHT,
-                                                Direction::RIGHT => Direction::LEFT,
-                                            };
-                                            if steer.direction() != opposite_dir {
-                                                p.set_head_direction(steer.direction())
-                                            }
-                                        }
-                                    }
+                    }
+                    self.update_timeout(addr);
+                }
+                game_message::Type::State(_state) => {
+                    self.send_ack(sockets, msg.msg_seq(), Some(msg.sender_id()), &addr);
+                    self.update_timeout(addr);
+                }
+                game_message::Type::Steer(steer) => {
+                    self.send_ack(sockets, msg.msg_seq(), Some(msg.sender_id()), &addr);
+                    if let Some(id) = self.addrs.get(&addr) {
+                        if let Some(player) = self.game.players.get(id) {
+                            let last_seq = self.steer_seqs.get(id).copied().unwrap_or(-1);
+                            if player.role != Role::Viewer && msg.msg_seq() > last_seq {
+                                self.steer_seqs.insert(*id, msg.msg_seq());
+                                let direction = Direction::from(steer.direction());
+                                // head_direction only changes on tick, so it is the direction the snake last moved in
+                                if self.game.can_steer(*id, direction) {
+                                    self.steers.insert(*id, direction);
                                 }
                             }
-                            self.update_timeout(addr);
                         }
                     }
+                    self.update_timeout(addr);
                 }
             }
         }
     }
 
-    fn add_local_player(&mut self, name: &str) -> Client {
-        let server_addr = self.sockets.socket.local_addr().expect("server socket has local addr");
+    fn add_local_player(&mut self, name: &str, server_port: u16) -> Client {
         let local_client = Client::new(
-            self.game.config.clone(),
+            GameConfig::from(&self.game.config),
             String::from(name),
-            0,
+            self.name.clone(),
+            self.id,
             NodeRole::MASTER,
-            server_addr,
+            SocketAddr::from((Ipv4Addr::LOCALHOST, server_port)),
         );
         let client_addr = local_client.get_local_addr();
-        let mut player = GamePlayer::new();
-        player.set_name("Admin".to_owned());
-        player.set_ip_address(client_addr.to_string());
-        player.set_score(0);
-        player.set_role(NodeRole::MASTER); // TODO: change
-        player.set_type(crate::snakes::snakes::PlayerType::HUMAN);
-        player.set_id(0);
-        if let Some((head, tail, dir)) = self.game.get_free_coord5x5() {
-            self.game.world[head.y() as usize][head.x() as usize] = WorldCell::Snake;
-            self.game.world[tail.y() as usize][tail.x() as usize] = WorldCell::Snake;
-            let mut snake = Snake::new();
-            snake.points.push(tail);
-            snake.points.push(head);
-            snake.set_head_direction(dir);
-            snake.set_state(SnakeState::ALIVE);
-            snake.set_player_id(0);
-            self.game.snakes.insert(player.id(), snake);
+        if let Some(player) = self.game.players.get_mut(&self.id) {
+            player.role = Role::Master;
+        } else {
+            let mut player = Player::new(self.id, "Admin".to_owned(), Role::Master, PlayerKind::Human); // TODO: change
+            player.team = self.game.pick_team(None);
+            // the host still gets to watch when there is no room for its snake
+            let _ = self.game.spawn_snake(player.id);
+            self.game.players.insert(player.id, player);
         }
-        self.addrs.insert( client_addr, player.id());
-        self.game.players.insert(player.id(), player);
+        self.addrs.insert(client_addr, self.id);
         local_client
     }
 
+    fn check_deputy(&mut self) {
+        if self.game.players.values().any(|player| player.role == Role::Deputy) {
+            return;
+        }
+        let candidate = self.game.players.values_mut()
+            .filter(|player| matches!(player.role, Role::Normal | Role::Viewer) && player.kind == PlayerKind::Human)
+            .min_by_key(|player| player.role != Role::Normal);
+        if let Some(player) = candidate {
+            player.role = Role::Deputy;
+            let id = player.id;
+            if let Some(addr) = self.addr_of(id) {
+                let gm = self.get_chnge_role(NodeRole::DEPUTY, id);
+                self.send(&gm, addr);
+            }
+        }
+    }
+
+    fn hand_over(&mut self, sockets: &Sockets) {
+        let deputy = self.game.players.values().find(|player| player.role == Role::Deputy).map(|player| player.id);
+        if let Some(id) = deputy {
+            if let Some(addr) = self.addr_of(id) {
+                let mut gm = GameMessage::new();
+                let mut rc = RoleChangeMsg::new();
+                rc.set_sender_role(NodeRole::VIEWER);
+                rc.set_receiver_role(NodeRole::MASTER);
+                gm.set_role_change(rc);
+                gm.set_msg_seq(self.seq);
+                gm.set_sender_id(self.id);
+                gm.set_receiver_id(id);
+                self.seq += 1;
+                let _ = sockets.socket.send_to(&gm.write_to_bytes().expect("role change bytes"), addr);
+            }
+        }
+    }
+}
+
+impl Server {
+    pub fn new(config: GameConfig, name: String) -> Self {
+        Server {
+            rooms: vec![Room::new(config, name)],
+            sockets: Sockets::new(true),
+            seq: 0,
+            multicast_instant: Instant::now(),
+        }
+    }
+
+    pub fn from_state(state: GameState, config: GameConfig, name: String) -> Result<Self> {
+        Ok(Server {
+            rooms: vec![Room::from_state(state, config, name)?],
+            sockets: Sockets::new(true),
+            seq: 0,
+            multicast_instant: Instant::now(),
+        })
+    }
+
+    pub fn add_game(&mut self, config: GameConfig, name: String) -> Result<()> {
+        if self.rooms.iter().any(|room| room.name == name) {
+            bail!("Game with name {} already exists", name);
+        }
+        validate::check(&config)?;
+        self.rooms.push(Room::new(config, name));
+        Ok(())
+    }
+
+    /// Adds robot players to the named room, they show up as ROBOT players to everyone else.
+    pub fn add_bots(&mut self, game_name: &str, count: usize, strategy: &str) -> Result<()> {
+        let Some(room) = self.rooms.iter_mut().find(|room| room.name == game_name) else {
+            bail!("No game with name {} was found", game_name);
+        };
+        for i in 1..=count {
+            let Some(strategy) = bot::by_name(strategy, rand::random()) else {
+                bail!("Unknown bot strategy {}, expected one of {}", strategy, bot::STRATEGIES.join(", "));
+            };
+            room.add_bot(format!("Bot {}", i), strategy);
+        }
+        Ok(())
+    }
+
+    pub fn take_over(&mut self, id: i32, sockets: Sockets) {
+        sockets.socket.set_nonblocking(true).expect("Successful non blocking main socket");
+        self.sockets = sockets;
+        for room in self.rooms.iter_mut() {
+            room.take_over(id);
+        }
+    }
+
+    fn get_announcement(&mut self) -> GameMessage {
+        let mut announcement = AnnouncementMsg::new();
+        announcement.games = self.rooms.iter().map(|room| room.get_announcement()).collect();
+        let mut game_message = GameMessage::new();
+        game_message.set_msg_seq(self.seq);
+        self.seq += 1;
+        // game_message.set_receiver_id(0);
+        game_message.set_announcement(announcement);
+        game_message
+    }
+
+    fn do_multicast(&mut self) {
+        let now = Instant::now();
+        if (now - self.multicast_instant).as_millis() > 1000 {
+            let announcement = self.get_announcement();
+            self.sockets.socket.send_to(&announcement.write_to_bytes().expect("announcement bytes"), "239.192.0.4:9192").expect("multicast send");
+            self.multicast_instant = now;
+        }
+    }
+
+    fn send_error(&mut self, message: &str, addr: SocketAddr) {
+        let mut error = ErrorMsg::new();
+        error.set_error_message(message.to_string());
+        let mut game_msg = GameMessage::new();
+        game_msg.set_msg_seq(self.seq);
+        game_msg.set_error(error);
+        self.seq += 1;
+        if let Ok(bytes) = game_msg.write_to_bytes() {
+            let _ = self.sockets.socket.send_to(&bytes, addr);
+        }
+    }
+
+    fn receive_message(&mut self) {
+        let mut buf = [0u8; MAX_DATAGRAM];
+        if let Ok((len, addr)) = self.sockets.socket.recv_from(&mut buf) {
+            if let Ok(msg) = GameMessage::parse_from_bytes(&buf[..len]) {
+                match &msg.Type {
+                    Some(game_message::Type::Discover(_discover)) => {
+                        let announcement = self.get_announcement();
+                        if let Ok(bytes) = announcement.write_to_bytes() {
+                            let _ = self.sockets.socket.send_to(&bytes, addr);
+                        }
+                    }
+                    Some(game_message::Type::Join(join)) => {
+                        if let Some(room) = self.rooms.iter_mut().find(|room| room.name == join.game_name()) {
+                            room.join(&self.sockets, &msg, join, addr);
+                        } else {
+                            self.send_error(&format!("No game with name {} was found", join.game_name()), addr);
+                        }
+                    }
+                    _ => {
+                        if let Some(room) = self.rooms.iter_mut().find(|room| room.owns(&addr)) {
+                            room.receive_message(&self.sockets, msg, addr);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     pub fn run(&mut self, player_name: &str) {
-        let mut client = self.add_local_player(player_name);
+        let server_port = self.sockets.socket.local_addr().expect("server socket has local addr").port();
+        let mut client = self.rooms[0].add_local_player(player_name, server_port);
         client.prepare();
         loop {
             self.receive_message();
-            self.do_state();
-            self.check_pending();
-            self.check_timeout();
+            for room in self.rooms.iter_mut() {
+                room.do_state();
+                room.check_pending(&self.sockets);
+                room.check_timeout();
+                room.check_deputy();
+            }
             self.do_multicast();
             client.action();
+            if client.quitting() {
+                for room in self.rooms.iter_mut() {
+                    room.hand_over(&self.sockets);
+                }
+                break;
+            }
         }
     }
 }
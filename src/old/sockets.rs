@@ -1,4 +1,9 @@
-use std::net::{UdpSocket};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+
+use crate::snakes::snakes::GamePlayer;
+
+/// Largest UDP payload over IPv4; announcements with maps and states with many players don't fit in less.
+pub const MAX_DATAGRAM: usize = 65507;
 
 pub struct Sockets {
     pub socket: UdpSocket,
@@ -30,3 +35,14 @@ impl Sockets {
     }   
 
 }
+
+pub fn set_player_addr(player: &mut GamePlayer, addr: SocketAddr) {
+    player.set_ip_address(addr.ip().to_string());
+    player.set_port(addr.port() as i32);
+}
+
+pub fn player_addr(player: &GamePlayer) -> Option<SocketAddr> {
+    let ip = player.ip_address().parse::<IpAddr>().ok()?;
+    let port = u16::try_from(player.port()).ok()?;
+    player.has_port().then_some(SocketAddr::new(ip, port))
+}
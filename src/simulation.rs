@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use crate::new::base::game::{Config, Direction, Game, Player, PlayerKind, Role};
+use crate::new::base::protobuf;
+use crate::snakes::snakes::{GameConfig, GameState};
+use crate::snakes::validate;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Death { player_id: i32 },
+    FoodEaten { player_id: i32, x: i32, y: i32 },
+    ScoreChanged { player_id: i32, score: i32, delta: i32 },
+    /// The tick ended the round, the next one starts a new round.
+    RoundOver { round: u32, winner: Option<i32> },
+}
+
+pub struct Step {
+    pub state: GameState,
+    pub events: Vec<Event>,
+}
+
+/// Runs a game without sockets or a terminal: the caller drives every tick.
+pub struct Simulation {
+    game: Game,
+    player_id: i32,
+}
+
+impl Simulation {
+
+    pub fn new(config: GameConfig, seed: u64) -> anyhow::Result<Self> {
+        validate::check(&config)?;
+        Ok(Simulation {
+            game: Game::new(Config::from(&config), seed),
+            player_id: 0,
+        })
+    }
+
+    pub fn set_food_per_player(&mut self, food_per_player: f32) {
+        self.game.config.food_per_player = food_per_player;
+    }
+
+    /// Adds a playing snake, or returns None when there is no room to spawn it.
+    pub fn add_player(&mut self, name: &str) -> Option<i32> {
+        let id = self.player_id;
+        self.game.spawn_snake(id).ok()?;
+        self.player_id += 1;
+        let mut player = Player::new(id, name.to_owned(), Role::Normal, PlayerKind::Robot);
+        player.team = self.game.pick_team(None);
+        self.game.players.insert(id, player);
+        Some(id)
+    }
+
+    /// Applies the steers the same way the master does (a later valid steer for the same
+    /// player wins, reversing into the neck is ignored) and advances the game by one tick.
+    pub fn tick(&mut self, steers: &[(i32, Direction)]) -> Step {
+        if self.game.round_over() {
+            for id in self.game.restart() {
+                if let Some(player) = self.game.players.get_mut(&id) {
+                    player.role = Role::Normal;
+                }
+            }
+        }
+
+        let mut directions = BTreeMap::<i32, Direction>::new();
+        for (id, direction) in steers {
+            if self.game.can_steer(*id, *direction) {
+                directions.insert(*id, *direction);
+            }
+        }
+        for (id, direction) in directions {
+            if let Some(snake) = self.game.snakes.get_mut(&id) {
+                snake.head_direction = direction;
+            }
+        }
+
+        let scores: BTreeMap<i32, i32> = self.game.players.iter().map(|(id, player)| (*id, player.score)).collect();
+        let tick = self.game.tick();
+
+        let mut events = Vec::new();
+        for (player_id, coord) in tick.eaten {
+            events.push(Event::FoodEaten { player_id, x: coord.x, y: coord.y });
+        }
+        for player_id in tick.dead {
+            if let Some(player) = self.game.players.get_mut(&player_id) {
+                player.role = Role::Viewer;
+            }
+            events.push(Event::Death { player_id });
+        }
+        for (player_id, player) in self.game.players.iter() {
+            let delta = player.score - scores.get(player_id).copied().unwrap_or(0);
+            if delta != 0 {
+                events.push(Event::ScoreChanged { player_id: *player_id, score: player.score, delta });
+            }
+        }
+        if self.game.round_over() {
+            let summary = self.game.summary();
+            events.push(Event::RoundOver { round: summary.round, winner: summary.winner });
+        }
+
+        Step {
+            state: protobuf::state(&self.game),
+            events,
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        protobuf::state(&self.game)
+    }
+
+    pub fn is_alive(&self, player_id: i32) -> bool {
+        self.game.snakes.contains_key(&player_id)
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::protobuf::Message;
+
+    use super::*;
+    use crate::new::base::game::{Coord, Snake, SnakeState};
+    use crate::snakes::ext;
+
+    fn config(width: i32, height: i32, food: i32) -> GameConfig {
+        let mut config = GameConfig::new();
+        config.set_width(width);
+        config.set_height(height);
+        config.set_food_static(food);
+        config.set_state_delay_ms(100);
+        config
+    }
+
+    /// Places a snake by hand, `body` goes from tail to head.
+    fn place(sim: &mut Simulation, body: &[(i32, i32)], direction: Direction) -> i32 {
+        let id = sim.player_id;
+        sim.player_id += 1;
+        sim.game.players.insert(id, Player::new(id, id.to_string(), Role::Normal, PlayerKind::Robot));
+        sim.game.add_snake(Snake {
+            head_direction: direction,
+            body: body.iter().map(|(x, y)| Coord::new(*x, *y)).collect(),
+            id,
+            state: SnakeState::Alive,
+        });
+        id
+    }
+
+    #[test]
+    fn eating_scores() {
+        let mut sim = Simulation::new(config(10, 10, 0), 0).unwrap();
+        let id = place(&mut sim, &[(2, 2), (3, 2)], Direction::RIGHT);
+        sim.game.add_food(Coord::new(4, 2));
+
+        let step = sim.tick(&[]);
+        assert_eq!(step.events, vec![
+            Event::FoodEaten { player_id: id, x: 4, y: 2 },
+            Event::ScoreChanged { player_id: id, score: 10, delta: 10 },
+        ]);
+        assert_eq!(sim.game().snakes[&id].body.len(), 3);
+        assert!(step.state.foods.is_empty());
+    }
+
+    #[test]
+    fn death_credits_the_body_owner() {
+        let mut sim = Simulation::new(config(10, 10, 0), 0).unwrap();
+        let owner = place(&mut sim, &[(5, 3), (5, 4), (5, 5)], Direction::DOWN);
+        let victim = place(&mut sim, &[(3, 4), (4, 4)], Direction::RIGHT);
+
+        let step = sim.tick(&[]);
+        assert_eq!(step.events, vec![
+            Event::Death { player_id: victim },
+            Event::ScoreChanged { player_id: owner, score: 10, delta: 10 },
+        ]);
+        assert!(!sim.is_alive(victim));
+        assert_eq!(sim.game().players[&victim].role, Role::Viewer);
+    }
+
+    #[test]
+    fn last_standing_round_ends_and_restarts() {
+        let mut config = config(10, 10, 0);
+        ext::set_mode(&mut config, 3, 0);
+        let mut sim = Simulation::new(config, 0).unwrap();
+        let owner = place(&mut sim, &[(5, 3), (5, 4), (5, 5)], Direction::DOWN);
+        let victim = place(&mut sim, &[(3, 4), (4, 4)], Direction::RIGHT);
+
+        let step = sim.tick(&[]);
+        assert_eq!(step.events.last(), Some(&Event::RoundOver { round: 1, winner: Some(owner) }));
+
+        let step = sim.tick(&[]);
+        assert!(!step.events.iter().any(|event| matches!(event, Event::RoundOver { .. })));
+        assert_eq!(sim.game().round, 2);
+        assert!(sim.is_alive(owner) && sim.is_alive(victim));
+        assert_eq!(sim.game().players[&owner].score, 0);
+    }
+
+    #[test]
+    fn same_seed_and_steers_replay_the_same_game() {
+        const TURNS: [Direction; 4] = [Direction::UP, Direction::LEFT, Direction::DOWN, Direction::RIGHT];
+        let play = |seed: u64| {
+            let mut sim = Simulation::new(config(20, 20, 5), seed).unwrap();
+            let ids: Vec<i32> = (0..3).filter_map(|i| sim.add_player(&i.to_string())).collect();
+            let mut states = vec![sim.state().write_to_bytes().unwrap()];
+            for tick in 0..60 {
+                let steers: Vec<(i32, Direction)> = ids.iter()
+                    .map(|id| (*id, TURNS[(tick / (3 + *id as usize)) % TURNS.len()]))
+                    .collect();
+                states.push(sim.tick(&steers).state.write_to_bytes().unwrap());
+            }
+            states
+        };
+        assert_eq!(play(7), play(7));
+    }
+}
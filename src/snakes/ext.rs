@@ -0,0 +1,158 @@
+//! Fields we add on top of snakes.proto. protoc isn't part of the build, so they travel as
+//! unknown fields with numbers well above the spec's; other implementations just skip them.
+
+use protobuf::{Message, UnknownValueRef};
+
+use super::snakes::{GameConfig, GamePlayer, GameState};
+use super::snakes::game_message::JoinMsg;
+
+const WALLED: u32 = 100;
+const MAP: u32 = 101;
+const MODE: u32 = 102;
+const MODE_GOAL: u32 = 103;
+const ROUND: u32 = 104;
+const ROUND_START: u32 = 105;
+const TEAMS: u32 = 106;
+const FRIENDLY_FIRE: u32 = 107;
+const TEAM: u32 = 108;
+const FOOD_PER_PLAYER: u32 = 109;
+const MAX_PLAYERS: u32 = 110;
+const STRATEGY: u32 = 111;
+const SEED: u32 = 112;
+
+fn set_varint<M: Message>(msg: &mut M, number: u32, value: Option<u64>) {
+    let fields = msg.mut_unknown_fields();
+    fields.remove(number);
+    if let Some(value) = value {
+        fields.add_varint(number, value);
+    }
+}
+
+fn varint<M: Message>(msg: &M, number: u32) -> Option<u64> {
+    match msg.unknown_fields().get(number) {
+        Some(UnknownValueRef::Varint(value)) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn set_walled(config: &mut GameConfig, walled: bool) {
+    set_varint(config, WALLED, walled.then_some(1));
+}
+
+/// Solid borders instead of the spec's toroidal field.
+pub fn walled(config: &GameConfig) -> bool {
+    varint(config, WALLED).is_some_and(|value| value != 0)
+}
+
+pub fn set_map(config: &mut GameConfig, map: Option<Vec<u8>>) {
+    let fields = config.mut_unknown_fields();
+    fields.remove(MAP);
+    if let Some(map) = map {
+        fields.add_length_delimited(MAP, map);
+    }
+}
+
+/// Encoded static map of the field, see `new::map::Map::to_bytes`.
+pub fn map(config: &GameConfig) -> Option<&[u8]> {
+    match config.unknown_fields().get(MAP) {
+        Some(UnknownValueRef::LengthDelimited(bytes)) => Some(bytes),
+        _ => None,
+    }
+}
+
+/// Round mode as a kind and a goal (seconds or score), see `new::base::game::Mode`.
+pub fn set_mode(config: &mut GameConfig, kind: u64, goal: u64) {
+    set_varint(config, MODE, (kind != 0).then_some(kind));
+    set_varint(config, MODE_GOAL, (kind != 0).then_some(goal));
+}
+
+pub fn mode(config: &GameConfig) -> (u64, u64) {
+    (varint(config, MODE).unwrap_or(0), varint(config, MODE_GOAL).unwrap_or(0))
+}
+
+pub fn set_round(state: &mut GameState, round: u32, round_start: i32) {
+    set_varint(state, ROUND, Some(round as u64));
+    set_varint(state, ROUND_START, Some(round_start as u64));
+}
+
+/// Current round and the state order it started at.
+pub fn round(state: &GameState) -> Option<(u32, i32)> {
+    Some((varint(state, ROUND)? as u32, varint(state, ROUND_START)? as i32))
+}
+
+pub fn set_seed(state: &mut GameState, seed: u64) {
+    set_varint(state, SEED, Some(seed));
+}
+
+/// Seed of the master's game, with the steers it replays the game.
+pub fn seed(state: &GameState) -> Option<u64> {
+    varint(state, SEED)
+}
+
+pub fn set_teams(config: &mut GameConfig, teams: u32, friendly_fire: bool) {
+    set_varint(config, TEAMS, (teams > 0).then_some(teams as u64));
+    set_varint(config, FRIENDLY_FIRE, (teams > 0 && friendly_fire).then_some(1));
+}
+
+/// Number of teams, 0 when everyone plays alone.
+pub fn teams(config: &GameConfig) -> u32 {
+    varint(config, TEAMS).map_or(0, |teams| teams.min(u32::MAX as u64) as u32)
+}
+
+pub fn friendly_fire(config: &GameConfig) -> bool {
+    varint(config, FRIENDLY_FIRE).is_some_and(|value| value != 0)
+}
+
+pub fn set_food_per_player(config: &mut GameConfig, food_per_player: f32) {
+    set_varint(config, FOOD_PER_PLAYER, (food_per_player > 0.0).then_some(food_per_player.to_bits() as u64));
+}
+
+/// Extra food per alive snake, sent as the bits of an f32.
+pub fn food_per_player(config: &GameConfig) -> f32 {
+    varint(config, FOOD_PER_PLAYER)
+        .map(|bits| f32::from_bits(bits as u32))
+        .filter(|food| food.is_finite() && *food > 0.0)
+        .unwrap_or(0.0)
+}
+
+pub fn set_max_players(config: &mut GameConfig, max_players: Option<usize>) {
+    set_varint(config, MAX_PLAYERS, max_players.map(|max_players| max_players as u64));
+}
+
+/// Cap on alive snakes, joins past it are refused.
+pub fn max_players(config: &GameConfig) -> Option<usize> {
+    varint(config, MAX_PLAYERS).map(|max_players| max_players.min(usize::MAX as u64) as usize)
+}
+
+pub fn set_team(player: &mut GamePlayer, team: Option<u32>) {
+    set_varint(player, TEAM, team.map(u64::from));
+}
+
+pub fn team(player: &GamePlayer) -> Option<u32> {
+    varint(player, TEAM).map(|team| team as u32)
+}
+
+/// Team asked for on join, the master picks one when it is missing or out of range.
+pub fn set_requested_team(join: &mut JoinMsg, team: Option<u32>) {
+    set_varint(join, TEAM, team.map(u64::from));
+}
+
+pub fn requested_team(join: &JoinMsg) -> Option<u32> {
+    varint(join, TEAM).map(|team| team as u32)
+}
+
+pub fn set_strategy(player: &mut GamePlayer, strategy: Option<&str>) {
+    let fields = player.mut_unknown_fields();
+    fields.remove(STRATEGY);
+    if let Some(strategy) = strategy {
+        fields.add_length_delimited(STRATEGY, strategy.as_bytes().to_vec());
+    }
+}
+
+/// Strategy of a robot steered by the master, so the next master keeps playing it the same way.
+pub fn strategy(player: &GamePlayer) -> Option<&str> {
+    match player.unknown_fields().get(STRATEGY) {
+        Some(UnknownValueRef::LengthDelimited(bytes)) => std::str::from_utf8(bytes).ok(),
+        _ => None,
+    }
+}
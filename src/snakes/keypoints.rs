@@ -0,0 +1,101 @@
+use anyhow::{bail, Result};
+
+use super::snakes::game_state::Coord;
+
+fn coord(x: i32, y: i32) -> Coord {
+    let mut coord = Coord::new();
+    coord.set_x(x);
+    coord.set_y(y);
+    coord
+}
+
+fn step(from: i32, to: i32) -> i32 {
+    // neighbours on the torus differ either by 1 or by (size - 1) when crossing an edge
+    match to - from {
+        d if d > 1 => -1,
+        d if d < -1 => 1,
+        d => d,
+    }
+}
+
+/// Compresses snake body cells (from head to tail) into snakes.proto key points:
+/// the head itself followed by offsets between consecutive turns.
+pub fn encode<I>(body: I) -> Vec<Coord>
+where I: IntoIterator<Item = (i32, i32)> {
+    let mut body = body.into_iter();
+    let mut points = Vec::new();
+    let Some(mut prev) = body.next() else {
+        return points;
+    };
+    points.push(coord(prev.0, prev.1));
+    let mut offset: Option<(i32, i32)> = None;
+    for cell in body {
+        let dx = step(prev.0, cell.0);
+        let dy = step(prev.1, cell.1);
+        offset = match offset {
+            Some((ox, oy)) if ox.signum() == dx && oy.signum() == dy => Some((ox + dx, oy + dy)),
+            Some((ox, oy)) => {
+                points.push(coord(ox, oy));
+                Some((dx, dy))
+            }
+            None => Some((dx, dy)),
+        };
+        prev = cell;
+    }
+    if let Some((ox, oy)) = offset {
+        points.push(coord(ox, oy));
+    }
+    points
+}
+
+/// Expands snakes.proto key points back into body cells (from head to tail),
+/// wrapping offsets that cross the field edge.
+pub fn decode(points: &[Coord], width: i32, height: i32) -> Result<Vec<(i32, i32)>> {
+    let mut body = Vec::new();
+    let Some((head, offsets)) = points.split_first() else {
+        return Ok(body);
+    };
+    let (mut x, mut y) = (head.x().rem_euclid(width), head.y().rem_euclid(height));
+    body.push((x, y));
+    for offset in offsets {
+        let (length, size) = match (offset.x(), offset.y()) {
+            (dx, 0) if dx != 0 => (dx.unsigned_abs(), width),
+            (0, dy) if dy != 0 => (dy.unsigned_abs(), height),
+            (dx, dy) => bail!("Key point offset ({}, {}) is not along one axis", dx, dy),
+        };
+        if length >= size as u32 {
+            bail!("Key point offset ({}, {}) is longer than the field", offset.x(), offset.y());
+        }
+        let (dx, dy) = (offset.x().signum(), offset.y().signum());
+        for _ in 0..length {
+            x = (x + dx).rem_euclid(width);
+            y = (y + dy).rem_euclid(height);
+            body.push((x, y));
+        }
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_across_edge() {
+        // head near the right edge, the body wraps to x = 0 and then turns down across the bottom edge
+        let body = vec![(8, 8), (9, 8), (0, 8), (1, 8), (1, 9), (1, 0), (1, 1), (2, 1)];
+        let points = encode(body.iter().copied());
+        let offsets: Vec<(i32, i32)> = points.iter().map(|point| (point.x(), point.y())).collect();
+        assert_eq!(offsets, vec![(8, 8), (3, 0), (0, 3), (1, 0)]);
+        assert_eq!(decode(&points, 10, 10).unwrap(), body);
+    }
+
+    #[test]
+    fn decode_rejects_bad_offsets() {
+        assert!(decode(&[coord(1, 1), coord(1, 1)], 10, 10).is_err());
+        assert!(decode(&[coord(1, 1), coord(0, 0)], 10, 10).is_err());
+        assert!(decode(&[coord(1, 1), coord(10, 0)], 10, 10).is_err());
+        assert!(decode(&[coord(1, 1), coord(0, i32::MIN)], 10, 10).is_err());
+        assert!(decode(&[coord(i32::MIN, 1), coord(i32::MIN, 0)], 10, 10).is_err());
+    }
+}
@@ -2,3 +2,6 @@
 
 
 pub mod snakes;
+pub mod keypoints;
+pub mod validate;
+pub mod ext;
\ No newline at end of file
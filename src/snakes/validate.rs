@@ -0,0 +1,61 @@
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+
+use crate::new::map::Map;
+
+use super::ext;
+use super::snakes::GameConfig;
+
+pub const WIDTH: RangeInclusive<i32> = 10..=100;
+pub const HEIGHT: RangeInclusive<i32> = 10..=100;
+pub const FOOD_STATIC: RangeInclusive<i32> = 0..=100;
+pub const STATE_DELAY_MS: RangeInclusive<i32> = 100..=3000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigField {
+    Width,
+    Height,
+    FoodStatic,
+    StateDelayMs,
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub field: ConfigField,
+    pub value: i32,
+    pub range: RangeInclusive<i32>,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} must be in {}..={}, got {}", self.field, self.range.start(), self.range.end(), self.value)
+    }
+}
+
+/// Checks GameConfig against the ranges given in snakes.proto.
+pub fn validate(config: &GameConfig) -> Vec<ConfigError> {
+    [
+        (ConfigField::Width, config.width(), WIDTH),
+        (ConfigField::Height, config.height(), HEIGHT),
+        (ConfigField::FoodStatic, config.food_static(), FOOD_STATIC),
+        (ConfigField::StateDelayMs, config.state_delay_ms(), STATE_DELAY_MS),
+    ]
+    .into_iter()
+    .filter(|(_, value, range)| !range.contains(value))
+    .map(|(field, value, range)| ConfigError { field, value, range })
+    .collect()
+}
+
+/// Also decodes the map extension, a node that can't read the map would play without its walls.
+pub fn check(config: &GameConfig) -> anyhow::Result<()> {
+    let errors = validate(config);
+    if !errors.is_empty() {
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        anyhow::bail!("Invalid game config: {}", messages.join(", "))
+    }
+    if let Some(bytes) = ext::map(config) {
+        Map::from_bytes(bytes, config.width() as usize, config.height() as usize)
+            .map_err(|e| anyhow::anyhow!("Invalid game config: {}", e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+
+use crate::new::bot::{self, BotStrategy, View};
+use crate::simulation::{Event, Simulation};
+use crate::snakes::snakes::GameConfig;
+
+pub struct Settings {
+    pub config: GameConfig,
+    /// One snake per entry, a strategy may be listed more than once.
+    pub strategies: Vec<String>,
+    pub matches: u64,
+    /// Match `i` runs with seed `seed + i`, so a tournament can be replayed exactly.
+    pub seed: u64,
+    /// A match still running after this many ticks goes to the longest snake.
+    pub max_ticks: u32,
+    pub food_per_player: f32,
+}
+
+/// Totals for one strategy over all matches.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub strategy: String,
+    pub snakes: u64,
+    pub wins: u64,
+    pub length: u64,
+    pub food: u64,
+    pub ticks: u64,
+}
+
+impl Stats {
+    fn per_snake(&self, total: u64) -> f64 {
+        if self.snakes == 0 { 0.0 } else { total as f64 / self.snakes as f64 }
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        self.per_snake(self.wins)
+    }
+
+    /// Length at death, or at the end of the match for survivors.
+    pub fn avg_length(&self) -> f64 {
+        self.per_snake(self.length)
+    }
+
+    pub fn avg_food(&self) -> f64 {
+        self.per_snake(self.food)
+    }
+
+    pub fn avg_ticks(&self) -> f64 {
+        self.per_snake(self.ticks)
+    }
+}
+
+struct Entrant {
+    id: i32,
+    strategy: Box<dyn BotStrategy>,
+    length: usize,
+    food: u64,
+    ticks: u64,
+}
+
+/// Plays one match and returns the entrants in the order of `Settings::strategies` with the winner's index.
+fn play(settings: &Settings, seed: u64) -> Result<(Vec<Entrant>, Option<usize>)> {
+    let mut sim = Simulation::new(settings.config.clone(), seed)?;
+    sim.set_food_per_player(settings.food_per_player);
+    let mut entrants = Vec::new();
+    for (i, name) in settings.strategies.iter().enumerate() {
+        let Some(strategy) = bot::by_name(name, seed.wrapping_add(i as u64)) else {
+            bail!("Unknown bot strategy {}, expected one of {}", name, bot::STRATEGIES.join(", "));
+        };
+        let Some(id) = sim.add_player(name) else {
+            bail!("No space to spawn {} snakes", settings.strategies.len());
+        };
+        entrants.push(Entrant { id, strategy, length: 2, food: 0, ticks: 0 });
+    }
+
+    for _ in 0..settings.max_ticks {
+        let alive: Vec<usize> = (0..entrants.len()).filter(|i| sim.is_alive(entrants[*i].id)).collect();
+        if (alive.len() <= 1 && entrants.len() > 1) || alive.is_empty() {
+            break;
+        }
+        let mut steers = Vec::new();
+        for i in alive {
+            let entrant = &mut entrants[i];
+            entrant.ticks += 1;
+            entrant.length = sim.game().snakes.get(&entrant.id).map_or(entrant.length, |snake| snake.body.len());
+            steers.push((entrant.id, entrant.strategy.steer(&View::new(sim.game(), entrant.id))));
+        }
+        for event in sim.tick(&steers).events {
+            if let Event::FoodEaten { player_id, .. } = event {
+                if let Some(entrant) = entrants.iter_mut().find(|entrant| entrant.id == player_id) {
+                    entrant.food += 1;
+                }
+            }
+        }
+    }
+    for entrant in entrants.iter_mut() {
+        if let Some(snake) = sim.game().snakes.get(&entrant.id) {
+            entrant.length = snake.body.len();
+        }
+    }
+
+    let alive: Vec<usize> = (0..entrants.len()).filter(|i| sim.is_alive(entrants[*i].id)).collect();
+    let longest = alive.iter().map(|i| entrants[*i].length).max();
+    let leaders: Vec<usize> = alive.into_iter().filter(|i| Some(entrants[*i].length) == longest).collect();
+    let winner = match leaders.as_slice() {
+        [winner] => Some(*winner),
+        _ => None,
+    };
+    Ok((entrants, winner))
+}
+
+/// Runs every match without sockets or a terminal, results are sorted by strategy name.
+pub fn run(settings: &Settings) -> Result<Vec<Stats>> {
+    let mut stats = BTreeMap::<String, Stats>::new();
+    for name in settings.strategies.iter() {
+        stats.entry(name.clone()).or_insert_with(|| Stats { strategy: name.clone(), ..Default::default() });
+    }
+    for i in 0..settings.matches {
+        let (entrants, winner) = play(settings, settings.seed.wrapping_add(i))?;
+        for (index, (entrant, name)) in entrants.iter().zip(settings.strategies.iter()).enumerate() {
+            let stats = stats.get_mut(name).expect("stats for every strategy");
+            stats.snakes += 1;
+            stats.wins += (winner == Some(index)) as u64;
+            stats.length += entrant.length as u64;
+            stats.food += entrant.food;
+            stats.ticks += entrant.ticks;
+        }
+    }
+    Ok(stats.into_values().collect())
+}
+
+pub fn to_json(settings: &Settings, stats: &[Stats]) -> Value {
+    json!({
+        "matches": settings.matches,
+        "seed": settings.seed,
+        "max_ticks": settings.max_ticks,
+        "width": settings.config.width(),
+        "height": settings.config.height(),
+        "food": settings.config.food_static(),
+        "food_per_player": settings.food_per_player,
+        "strategies": stats.iter().map(|stats| json!({
+            "strategy": stats.strategy,
+            "snakes": stats.snakes,
+            "wins": stats.wins,
+            "win_rate": stats.win_rate(),
+            "avg_length": stats.avg_length(),
+            "avg_food": stats.avg_food(),
+            "avg_survival_ticks": stats.avg_ticks(),
+        })).collect::<Vec<Value>>(),
+    })
+}
+
+pub fn to_csv(stats: &[Stats]) -> String {
+    let mut csv = String::from("strategy,snakes,wins,win_rate,avg_length,avg_food,avg_survival_ticks\n");
+    for stats in stats {
+        csv.push_str(&format!(
+            "{},{},{},{:.4},{:.2},{:.2},{:.2}\n",
+            stats.strategy, stats.snakes, stats.wins, stats.win_rate(), stats.avg_length(), stats.avg_food(), stats.avg_ticks(),
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(strategies: &[&str]) -> Settings {
+        let mut config = GameConfig::new();
+        config.set_width(20);
+        config.set_height(20);
+        config.set_food_static(5);
+        config.set_state_delay_ms(100);
+        Settings {
+            config,
+            strategies: strategies.iter().map(|name| name.to_string()).collect(),
+            matches: 3,
+            seed: 1,
+            max_ticks: 200,
+            food_per_player: 0.0,
+        }
+    }
+
+    #[test]
+    fn seeded_run_is_repeatable() {
+        let settings = settings(&["greedy", "flood", "random"]);
+        let stats = run(&settings).unwrap();
+        let names: Vec<&str> = stats.iter().map(|stats| stats.strategy.as_str()).collect();
+        assert_eq!(names, vec!["flood", "greedy", "random"]);
+        assert!(stats.iter().all(|stats| stats.snakes == settings.matches));
+        assert!(stats.iter().map(|stats| stats.wins).sum::<u64>() <= settings.matches);
+        assert_eq!(to_csv(&stats), to_csv(&run(&settings).unwrap()));
+    }
+
+    #[test]
+    fn unknown_strategy_fails() {
+        assert!(run(&settings(&["greedy", "teleport"])).is_err());
+    }
+}
@@ -1,9 +1,10 @@
 use std::{collections::{HashMap}, net::{SocketAddr, UdpSocket, Ipv4Addr}, time::Instant};
 
-use crate::{old::{client::Client}, snakes::snakes::{GameMessage, game_message::{self}, NodeRole}, tui::{err::print_error, modal::show_modal}};
+use crate::new::base::{game::{Mode, MAX_TEAMS}, protobuf};
+use crate::{old::{client::Client, sockets::MAX_DATAGRAM}, snakes::{ext, snakes::{GameAnnouncement, GameMessage, game_message::{self}, NodeRole}}, tui::{err::print_error, modal::show_modal}};
 
 use ncurses::*;
-use protobuf::Message;
+use ::protobuf::Message;
 use std::hash::Hash;
 
 struct GameOption {
@@ -31,8 +32,8 @@ pub fn browse(player_name: &str) -> Option<Client> {
     multicast_receiver
         .join_multicast_v4(&Ipv4Addr::new(239, 192, 0, 4), &Ipv4Addr::new(0, 0, 0, 0))
         .expect("Successful join");
-    let mut server_list = HashMap::<GameOption, Instant>::new();
-    let mut buf = [0;1024];
+    let mut server_list = HashMap::<GameOption, (Instant, GameAnnouncement)>::new();
+    let mut buf = [0; MAX_DATAGRAM];
 
     let mut selected = 0;
     const SERVER_PAIR: i16 = 1;
@@ -50,9 +51,8 @@ pub fn browse(player_name: &str) -> Option<Client> {
                     match tpe {
                         game_message::Type::Announcement(an) => {
                             for game in an.games {
-                                if game.can_join() {
-                                    server_list.insert(GameOption { name: game.game_name.or(Some("Annonymous".to_owned())).unwrap(), addr: addr.clone()}, Instant::now());
-                                }
+                                let name = game.game_name.clone().unwrap_or("Annonymous".to_owned());
+                                server_list.insert(GameOption { name, addr }, (Instant::now(), game));
                             }
                         }
                         _ => {}
@@ -61,7 +61,7 @@ pub fn browse(player_name: &str) -> Option<Client> {
             }
         }
         let mut i = 0;
-        server_list.retain(|game, ins| {
+        server_list.retain(|game, (ins, announcement)| {
             if ins.elapsed().as_secs_f32() > 1.0f32 {
                 if selected >= i {
                     selected -= 1;
@@ -77,6 +77,31 @@ pub fn browse(player_name: &str) -> Option<Client> {
                 addstr(&format!("{} {}", game.name, game.addr.to_string()));
                 attroff(COLOR_PAIR(SERVER_PAIR));
             }
+            let mode = protobuf::mode_from_proto(&announcement.config);
+            if mode != Mode::Endless {
+                addstr(&format!(" [{}]", mode));
+            }
+            let teams = ext::teams(&announcement.config);
+            if teams > 0 {
+                let mut scores: Vec<i32> = vec![0; teams as usize];
+                for player in announcement.players.players.iter() {
+                    let index = ext::team(player).and_then(|team| (team as usize).checked_sub(1));
+                    if let Some(score) = index.and_then(|index| scores.get_mut(index)) {
+                        *score += player.score();
+                    }
+                }
+                let scores: Vec<String> = scores.iter().map(|score| score.to_string()).collect();
+                addstr(&format!(" [teams {}]", scores.join(":")));
+                if ext::friendly_fire(&announcement.config) {
+                    addstr(" [friendly fire]");
+                }
+            }
+            if ext::walled(&announcement.config) {
+                addstr(" [walled]");
+            }
+            if !announcement.can_join() {
+                addstr(" [full]");
+            }
             addstr("\n");
 
             i += 1;
@@ -101,8 +126,13 @@ pub fn browse(player_name: &str) -> Option<Client> {
                 if len == 0 {
                     return None;
                 }
-                let key = server_list.keys().nth(selected).expect("selected variant exists");
-                let role = match show_modal("Select role", vec!["Cancel", "Player", "Viewer"]) {
+                let (key, (_, announcement)) = server_list.iter().nth(selected).expect("selected variant exists");
+                let options = if announcement.can_join() {
+                    vec!["Cancel", "Player", "Viewer"]
+                } else {
+                    vec!["Cancel", "Viewer"]
+                };
+                let role = match show_modal("Select role", options) {
                     "Player" => {
                         NodeRole::NORMAL
                     }
@@ -113,7 +143,17 @@ pub fn browse(player_name: &str) -> Option<Client> {
                         continue;
                     }
                 };
-                match Client::join( key.addr.clone(), &key.name, player_name, role) {
+                let teams = ext::teams(&announcement.config).min(MAX_TEAMS) as usize;
+                let team = if teams > 0 && role == NodeRole::NORMAL {
+                    let options = ["Auto", "1", "2", "3", "4"][..=teams].to_vec();
+                    match show_modal("Select team", options) {
+                        "Auto" => None,
+                        team => team.parse().ok(),
+                    }
+                } else {
+                    None
+                };
+                match Client::join(key.addr, &key.name, player_name, role, team) {
                     Ok(cl) => return Some(cl),
                     Err(e) => {
                         print_error(format!("Failed to connect to server: {:?}", e));
@@ -1,28 +1,28 @@
 use ncurses::*;
-use crate::tui::err::print_error;
 
 pub struct NumInput<'a> {
     pub name: &'a str,
     pub value: i32,
     pub raw: String,
+    pub error: Option<String>,
     is_number: bool,
 }
 
 impl NumInput<'_> {
     pub fn new<'a>(name: &'a str) -> NumInput<'a> {
-        NumInput { name, value: i32::default(), raw: String::new(), is_number: true }
+        NumInput { name, value: i32::default(), raw: String::new(), error: None, is_number: true }
     }
     
     pub fn default<'a>(name: &'a str, value: i32) -> NumInput<'a> {
-        NumInput { name, value: value, raw: value.to_string(), is_number: true }
+        NumInput { name, value: value, raw: value.to_string(), error: None, is_number: true }
     }
     
     pub fn str<'a>(name: &'a str) -> NumInput<'a> {
-        NumInput { name, value: i32::default(), raw: String::new(), is_number: false }
+        NumInput { name, value: i32::default(), raw: String::new(), error: None, is_number: false }
     }
     
     pub fn str_default<'a>(name: &'a str, value: &'a str) -> NumInput<'a> {
-        NumInput { name, value: i32::default(), raw: String::from(value), is_number: false }
+        NumInput { name, value: i32::default(), raw: String::from(value), error: None, is_number: false }
     }
 
 }
@@ -35,11 +35,13 @@ pub fn show_menu_config(inputs: &mut Vec<NumInput>) -> Result<(), ()> {
     const INPUT_SELECTED_PAIR: i16 = 2;
     const SUMBMIT_PAIR: i16 = 3;
     const CANCEL_PAIR: i16 = 4;
+    const ERROR_PAIR: i16 = 8;
     start_color();
     init_pair(INPUT_PAIR, COLOR_WHITE, COLOR_BLACK | 0b1000);
     init_pair(INPUT_SELECTED_PAIR, COLOR_WHITE, COLOR_BLUE);
     init_pair(SUMBMIT_PAIR, COLOR_BLACK, COLOR_GREEN | 0b1000);
     init_pair(CANCEL_PAIR, COLOR_BLACK, COLOR_RED | 0b1000);
+    init_pair(ERROR_PAIR, COLOR_RED, COLOR_BLACK);
     let buttons_row = inputs.len();
     let mut selected_button = 0;
     
@@ -59,6 +61,11 @@ pub fn show_menu_config(inputs: &mut Vec<NumInput>) -> Result<(), ()> {
                 // e.print_nc();
                 attroff(COLOR_PAIR(INPUT_PAIR));
             }
+            if let Some(error) = &e.error {
+                attron(COLOR_PAIR(ERROR_PAIR));
+                addstr(&format!(" {}", error));
+                attroff(COLOR_PAIR(ERROR_PAIR));
+            }
             addstr("\n");
         }
         if selected == buttons_row {
@@ -84,12 +91,14 @@ pub fn show_menu_config(inputs: &mut Vec<NumInput>) -> Result<(), ()> {
         refresh();
 
         let key = getch();
+        // w and s only move between rows when they can't be typed into the selected input
+        let typing = inputs.get(selected).is_some_and(|input| !input.is_number);
         match key {
-            KEY_UP | 119 => {
+            KEY_UP | 119 if key == KEY_UP || !typing => {
                 selected = selected + len - 1;
                 selected %= len;
             }
-            KEY_DOWN | 115 => {
+            KEY_DOWN | 115 if key == KEY_DOWN || !typing => {
                 selected += 1;
                 selected %= len;
             }
@@ -103,7 +112,7 @@ pub fn show_menu_config(inputs: &mut Vec<NumInput>) -> Result<(), ()> {
                 let selected_raw = &mut inputs[selected].raw;
                 selected_raw.push(char::from_u32(key as u32).unwrap());
             }
-            97..=122 | 65..=90 | 95 | 32 => { 
+            97..=122 | 65..=90 | 95 | 32 | 45..=47 => { // letters, "_", " ", "-", ".", "/"
                 if !inputs[selected].is_number {
                     let selected_raw = &mut inputs[selected].raw;
                     selected_raw.push(char::from_u32(key as u32).unwrap());
@@ -123,12 +132,12 @@ pub fn show_menu_config(inputs: &mut Vec<NumInput>) -> Result<(), ()> {
                         1 => {
                             let mut all_valid = true;
                             for e in inputs.iter_mut() {
+                                e.error = None;
                                 if e.is_number {
                                     match e.raw.parse() {
-                                        Err(e) => {
-                                            print_error(e);
+                                        Err(err) => {
+                                            e.error = Some(format!("{}", err));
                                             all_valid = false;
-                                            break;
                                         }
                                         Ok(rs) => {
                                             e.value = rs;
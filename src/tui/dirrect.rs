@@ -122,7 +122,7 @@ pub fn show_connect_dialog(player_name: &str) -> Option<Client> {
                                     continue;
                                 }
                             };
-                            match Client::join(&inputs[0].raw, &inputs[1].raw, player_name, role) {
+                            match Client::join(&inputs[0].raw, &inputs[1].raw, player_name, role, None) {
                                 Ok(client) => return Some(client),
                                 Err(e) => {
                                     print_error(e);
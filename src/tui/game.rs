@@ -1,8 +1,6 @@
 use ncurses::*;
 
-use crate::snakes::snakes::game_state::snake::SnakeState;
-use crate::snakes::snakes::{GameConfig, GamePlayer};
-use crate::old::base::Game;
+use crate::new::base::game::{Config, Game, Player, RoundSummary, SnakeState};
 
 const STATS_HEIGHT: i32 = 3;
 
@@ -36,14 +34,23 @@ pub struct GameInterface {
 }
 
 impl GameInterface {
-    pub fn new(cfg: &GameConfig) -> Self {
+    pub fn new(cfg: &Config) -> Self {
         let mut max_x = 0;
         let mut max_y = 0;
         getmaxyx(stdscr(), &mut max_y, &mut max_x);
+        let width = cfg.width as i32;
+        let height = cfg.height as i32;
         let (b_stats, stats) = create_win(0, 0, max_x - 2, STATS_HEIGHT);
-        let (b_main, main) = create_win(0, STATS_HEIGHT + 2, cfg.width(), cfg.height());
-        let (b_players, players) = create_win(cfg.width() + 2, STATS_HEIGHT + 2, max_x - cfg.width() - 4, cfg.height());
-        let (b_dbg, dbg) = create_win(0, cfg.height() + STATS_HEIGHT + 4, max_x - 2, max_y - cfg.height() - STATS_HEIGHT - 6);
+        let (b_main, main) = create_win(0, STATS_HEIGHT + 2, width, height);
+        let (b_players, players) = create_win(width + 2, STATS_HEIGHT + 2, max_x - width - 4, height);
+        let (b_dbg, dbg) = create_win(0, height + STATS_HEIGHT + 4, max_x - 2, max_y - height - STATS_HEIGHT - 6);
+        if cfg.walled {
+            const WALL_PAIR: i16 = 9;
+            init_pair(WALL_PAIR, COLOR_RED, COLOR_BLACK);
+            let wall = '#' as chtype | COLOR_PAIR(WALL_PAIR);
+            wborder(b_main, wall, wall, wall, wall, wall, wall, wall, wall);
+            wrefresh(b_main);
+        }
         let int = GameInterface {
             stats,
             b_stats,
@@ -58,27 +65,45 @@ impl GameInterface {
         return int;
     }
 
-    fn print_stats(&self, player: &GamePlayer) {
+    fn print_stats(&self, game: &Game, player: &Player) {
         wclear(self.stats);
         waddstr(self.stats, &format!("{}\t{}\t{}\t{}\n", "role", "id", "name", "score"));
-        waddstr(self.stats, &format!("{:?}\t{}\t{}\t{}", player.role(), player.id(), player.name(), player.score()));
+        waddstr(self.stats, &format!("{:?}\t{}\t{}\t{}\n", player.role, player.id, player.name, player.score));
+        waddstr(self.stats, &format!("{}\tround {}", game.config.mode, game.round));
+        if let Some(left) = game.time_left() {
+            waddstr(self.stats, &format!("\t{}s left", left.as_secs()));
+        }
+        waddstr(self.stats, &format!("\tseed {}", game.seed()));
         wrefresh(self.stats);
     }
 
     fn print_players<'a, I>(&self, players: I, self_id: i32) 
-        where I: Iterator<Item = &'a GamePlayer> {
-        wclear(self.players);
+        where I: Iterator<Item = &'a Player> {
         for player in players {
-            if player.id() == self_id {
+            if player.id == self_id {
                 wattron(self.players, A_BOLD());
-                waddstr(self.players, player.name());
+                waddstr(self.players, &player.name);
                 waddch(self.players, '\n'.into());
                 wattroff(self.players, A_BOLD());
             } else {
-                waddstr(self.players, player.name());
+                waddstr(self.players, &player.name);
                 waddch(self.players, '\n'.into());
             }
         }
+    }
+
+    fn print_teams(&self, game: &Game, self_id: i32) {
+        wclear(self.players);
+        if game.config.teams == 0 {
+            self.print_players(game.players.values(), self_id);
+        } else {
+            for (team, score) in game.team_scores() {
+                wattron(self.players, A_UNDERLINE());
+                waddstr(self.players, &format!("team {} {}\n", team, score));
+                wattroff(self.players, A_UNDERLINE());
+                self.print_players(game.players.values().filter(|player| player.team == Some(team)), self_id);
+            }
+        }
         wrefresh(self.players);
     }
 
@@ -93,31 +118,40 @@ impl GameInterface {
         init_pair(SELF_SNAK_PAIR, COLOR_BLUE, COLOR_BLACK);
         const ZM_SNAK_PAIR: i16 = 7;
         init_pair(ZM_SNAK_PAIR, COLOR_GREEN | 0b1000, COLOR_BLACK);
+        const TEAM_SNAK_PAIR: i16 = 10;
+        init_pair(TEAM_SNAK_PAIR, COLOR_CYAN, COLOR_BLACK);
+        let self_team = game.team_of(self_id);
+        for wall in game.config.map.iter().flat_map(|map| map.walls.iter()) {
+            wmove(self.main, wall.y, wall.x);
+            waddch(self.main, ACS_CKBOARD());
+        }
         wattron(self.main, COLOR_PAIR(FOOD_PAIR));
         for food in game.food.iter() {
-            let x = food.x();
-            let y = food.y();
+            let x = food.x;
+            let y = food.y;
             wmove(self.main, y, x);
             waddch(self.main, '@' as u32);
         }
         wattroff(self.main, COLOR_PAIR(FOOD_PAIR));
         for snak in game.snakes.values() {
-            let snak_attr = match snak.state() {
-                SnakeState::ZOMBIE => {
+            let snak_attr = match snak.state {
+                SnakeState::Zombie => {
                     COLOR_PAIR(ZM_SNAK_PAIR)
                 }
-                SnakeState::ALIVE => {
-                    if snak.player_id() == self_id {
+                SnakeState::Alive => {
+                    if snak.id == self_id {
                         COLOR_PAIR(SELF_SNAK_PAIR)
+                    } else if self_team.is_some() && game.team_of(snak.id) == self_team {
+                        COLOR_PAIR(TEAM_SNAK_PAIR)
                     } else {
                         COLOR_PAIR(EN_SNAK_PAIR)
                     }
                 }
             };
             wattron(self.main, snak_attr);
-            for coord in snak.points.iter() {
-                let x = coord.x();
-                let y = coord.y();
+            for coord in snak.body.iter() {
+                let x = coord.x;
+                let y = coord.y;
                 wmove(self.main, y, x);
                 waddch(self.main, '#' as u32);
             }
@@ -129,12 +163,28 @@ impl GameInterface {
 
     pub fn print(&self, game: &Game, self_id: i32) {
         if let Some(player) = game.players.get(&self_id) {
-            self.print_stats(player);
+            self.print_stats(game, player);
         }
-        self.print_players(game.players.values(), self_id);
+        self.print_teams(game, self_id);
         self.print_game(game, self_id);
     }
 
+    pub fn print_error(&self, msg: &str) {
+        const ERROR_PAIR: i16 = 8;
+        init_pair(ERROR_PAIR, COLOR_RED, COLOR_BLACK);
+        wattron(self.dbg, COLOR_PAIR(ERROR_PAIR));
+        waddstr(self.dbg, &format!("{}\n", msg));
+        wattroff(self.dbg, COLOR_PAIR(ERROR_PAIR));
+        wrefresh(self.dbg);
+    }
+
+    pub fn print_summary(&self, summary: &RoundSummary) {
+        wattron(self.dbg, A_BOLD());
+        waddstr(self.dbg, &summary.to_string());
+        wattroff(self.dbg, A_BOLD());
+        wrefresh(self.dbg);
+    }
+
     pub fn dbg(&self, msg: &str) {
         waddstr(self.dbg, msg);
         wrefresh(self.dbg);